@@ -1,6 +1,14 @@
-use crate::{Client, Database, MemoryDB, ResultAPI};
+use crate::{ApiError, BackendCache, Client, Database, ErrorBody, ErrorCode, MemoryDB};
+use crate::{ResultAPI, Storage};
 use actix_web::dev::HttpServiceFactory;
-use actix_web::{Responder, get, post, web};
+use actix_web::{Responder, delete, get, patch, post, web};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// The default number of clients per page
+const DEFAULT_PAGE_LIMIT: usize = 50;
+/// The maximum number of clients per page
+const MAX_PAGE_LIMIT: usize = 100;
 
 #[derive(Debug)]
 pub enum ClientRoute {
@@ -8,6 +16,23 @@ pub enum ClientRoute {
     CreateClient,
     /// Get a client
     GetClient,
+    /// Update a client
+    UpdateClient,
+    /// Delete a client
+    DeleteClient,
+    /// List all clients
+    ListClients,
+}
+
+impl ClientRoute {
+    /// Every client route
+    pub const ALL: [Self; 5] = [
+        Self::CreateClient,
+        Self::GetClient,
+        Self::UpdateClient,
+        Self::DeleteClient,
+        Self::ListClients,
+    ];
 }
 
 impl HttpServiceFactory for ClientRoute {
@@ -15,59 +40,271 @@ impl HttpServiceFactory for ClientRoute {
         match self {
             Self::CreateClient => create_client.register(config),
             Self::GetClient => get_client.register(config),
+            Self::UpdateClient => update_client.register(config),
+            Self::DeleteClient => delete_client.register(config),
+            Self::ListClients => list_clients.register(config),
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/client",
+    tag = "client",
+    request_body = Client,
+    responses(
+        (status = 200, description = "The created client", body = Client),
+        (status = 400, description = "Malformed request", body = ErrorBody),
+        (status = 409, description = "The wallet already has a client", body = ErrorBody),
+    )
+)]
 #[post("/client")]
 async fn create_client(
     web::ThinData(db): web::ThinData<MemoryDB>,
     web::Json(data): web::Json<Client>,
-) -> impl Responder {
+) -> Result<impl Responder, ApiError> {
     // Create a new client
     let client = Client::new(data.name, data.wallet);
 
     // Check for duplicate wallet
     if db.get_client(&client.id.unwrap()).is_ok() {
         tracing::warn!(?data.wallet, "Client already exists");
-        return ResultAPI::failure("Client already exists");
+        return Err(ApiError::from(ErrorCode::ClientExists));
     }
 
     // Insert to DB
     if let Err(error) = db.set_client(client.id.unwrap(), client.clone()) {
         tracing::error!(?error, ?client, "Failed to create client");
-        return ResultAPI::failure("Failed to create client");
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            "Failed to create client",
+        ));
     }
 
-    ResultAPI::okay(client)
+    Ok(ResultAPI::success(client))
 }
 
+#[utoipa::path(
+    get,
+    path = "/client/{id}",
+    tag = "client",
+    params(("id" = String, Path, description = "The wallet address of the client")),
+    responses(
+        (status = 200, description = "The client", body = Client),
+        (status = 400, description = "Invalid client ID", body = ErrorBody),
+        (status = 404, description = "The client does not exist", body = ErrorBody),
+    )
+)]
 #[get("/client/{id}")]
-async fn get_client(id: web::Path<String>, db: web::ThinData<MemoryDB>) -> impl Responder {
+async fn get_client(
+    id: web::Path<String>,
+    db: web::ThinData<MemoryDB>,
+) -> Result<impl Responder, ApiError> {
     // Parse the ID
     let id = match id.parse() {
         Ok(a) => a,
         Err(error) => {
             tracing::error!(?error, ?id, "Invalid ID format");
-            return ResultAPI::failure("Invalid ID format");
+            return Err(ApiError::from(ErrorCode::InvalidClientId));
         }
     };
 
     // Get the client
     match db.get_client(&id) {
-        Ok(client) => ResultAPI::okay(client),
+        Ok(client) => Ok(ResultAPI::success(client.redacted())),
         Err(error) => {
             tracing::error!(?error, ?id, "Failed to get client");
-            ResultAPI::failure("Client does not exist")
+            Err(ApiError::from(ErrorCode::ClientNotFound))
+        }
+    }
+}
+
+/// The request to update a client
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClientRequest {
+    /// The new nickname
+    pub name: Option<String>,
+    /// The new storage information
+    pub storage: Option<Storage<String>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/client/{id}",
+    tag = "client",
+    params(("id" = String, Path, description = "The wallet address of the client")),
+    request_body = UpdateClientRequest,
+    responses(
+        (status = 200, description = "The updated client", body = Client),
+        (status = 400, description = "Invalid client ID or request", body = ErrorBody),
+        (status = 404, description = "The client does not exist", body = ErrorBody),
+    )
+)]
+#[patch("/client/{id}")]
+async fn update_client(
+    id: web::Path<String>,
+    db: web::ThinData<MemoryDB>,
+    cache: web::ThinData<BackendCache>,
+    web::Json(payload): web::Json<UpdateClientRequest>,
+) -> Result<impl Responder, ApiError> {
+    // Parse the ID
+    let id = match id.parse() {
+        Ok(a) => a,
+        Err(error) => {
+            tracing::error!(?error, ?id, "Invalid ID format");
+            return Err(ApiError::from(ErrorCode::InvalidClientId));
+        }
+    };
+
+    // Get the client
+    let mut client = match db.get_client(&id) {
+        Ok(client) => client,
+        Err(error) => {
+            tracing::error!(?error, ?id, "Failed to get client");
+            return Err(ApiError::from(ErrorCode::ClientNotFound));
+        }
+    };
+
+    // Apply the changes
+    if let Some(name) = payload.name {
+        client.name = name;
+    }
+    if let Some(storage) = payload.storage {
+        // Drop cached credentials of the storage being replaced
+        if let Some(previous) = client.storage.replace(storage) {
+            cache.evict(&previous);
+        }
+    }
+
+    if let Err(error) = db.set_client(id, client.clone()) {
+        tracing::error!(?error, ?id, "Failed to update client");
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            "Failed to update client",
+        ));
+    }
+
+    Ok(ResultAPI::success(client.redacted()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/client/{id}",
+    tag = "client",
+    params(("id" = String, Path, description = "The wallet address of the client")),
+    responses(
+        (status = 200, description = "The deleted client", body = Client),
+        (status = 400, description = "Invalid client ID", body = ErrorBody),
+        (status = 404, description = "The client does not exist", body = ErrorBody),
+    )
+)]
+#[delete("/client/{id}")]
+async fn delete_client(
+    id: web::Path<String>,
+    db: web::ThinData<MemoryDB>,
+    cache: web::ThinData<BackendCache>,
+) -> Result<impl Responder, ApiError> {
+    // Parse the ID
+    let id = match id.parse() {
+        Ok(a) => a,
+        Err(error) => {
+            tracing::error!(?error, ?id, "Invalid ID format");
+            return Err(ApiError::from(ErrorCode::InvalidClientId));
+        }
+    };
+
+    // Delete the client and everything it owns
+    match db.delete_client(&id) {
+        Ok(client) => {
+            if let Some(storage) = &client.storage {
+                cache.evict(storage);
+            }
+            Ok(ResultAPI::success(client.redacted()))
+        }
+        Err(error) => {
+            tracing::error!(?error, ?id, "Failed to delete client");
+            Err(ApiError::from(ErrorCode::ClientNotFound))
         }
     }
 }
 
+/// The request to list clients
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct ListClientsRequest {
+    /// The number of clients to skip
+    pub offset: Option<usize>,
+    /// The maximum number of clients to return
+    pub limit: Option<usize>,
+}
+
+/// A page of clients
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientPage {
+    /// The clients in this page
+    pub clients: Vec<Client>,
+    /// The total number of clients
+    pub total: usize,
+    /// The offset of the next page, if any
+    pub next_offset: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/client",
+    tag = "client",
+    params(ListClientsRequest),
+    responses((status = 200, description = "A page of clients", body = ClientPage))
+)]
+#[get("/client")]
+async fn list_clients(
+    query: web::Query<ListClientsRequest>,
+    db: web::ThinData<MemoryDB>,
+) -> Result<impl Responder, ApiError> {
+    let offset = query.offset.unwrap_or_default();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+
+    let mut clients = match db.get_all_clients() {
+        Ok(c) => c,
+        Err(error) => {
+            tracing::error!(?error, "Failed to get all clients");
+            return Err(ApiError::new(
+                ErrorCode::InternalError,
+                "Failed to get all clients",
+            ));
+        }
+    };
+
+    // Sort by ID for stable pagination
+    clients.sort_by_key(|c| c.id);
+    let total = clients.len();
+    let clients = clients
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(Client::redacted)
+        .collect::<Vec<_>>();
+    let next_offset = Some(offset + clients.len()).filter(|n| *n < total);
+
+    Ok(ResultAPI::success(ClientPage {
+        clients,
+        total,
+        next_offset,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::tests::offline_sts;
     use actix_web::{App, http::StatusCode, test, web::ThinData};
-    use alloy_primitives::address;
+    use alloy_primitives::{Address, address};
 
     #[actix_web::test]
     async fn test_create_client_api() -> anyhow::Result<()> {
@@ -98,6 +335,14 @@ mod tests {
         assert_eq!(data.name, body.name);
         assert_eq!(data.wallet, wallet);
         assert_eq!(data.wallet, body.wallet);
+
+        // Duplicate wallets conflict
+        let req = test::TestRequest::post()
+            .uri("/client")
+            .set_json(&client)
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
         Ok(())
     }
 
@@ -129,4 +374,145 @@ mod tests {
         assert_eq!(body.wallet, wallet);
         Ok(())
     }
+
+    #[actix_web::test]
+    async fn test_client_secrets_redacted() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let app = App::new()
+            .app_data(db.clone())
+            .service(get_client)
+            .service(list_clients);
+        let server = test::init_service(app).await;
+
+        // Insert a client with static credentials
+        let wallet = address!("0xc0ffee1234567890123456789012345678901234");
+        let mut client = Client::new(String::from("platformA"), wallet);
+        client.storage = Some(Storage::S3Compatible {
+            endpoint: String::from("http://localhost:9000"),
+            region: String::from("auto"),
+            access_key_id: String::from("minio"),
+            secret_access_key: String::from("minio-secret"),
+            path_style: true,
+        });
+        db.set_client(wallet, client)?;
+
+        // The secret is never returned
+        for url in [format!("/client/{wallet}"), String::from("/client")] {
+            let req = test::TestRequest::get().uri(&url).to_request();
+            let body = test::call_and_read_body(&server, req).await;
+            let body = String::from_utf8(body.to_vec())?;
+            assert!(body.contains("minio"));
+            assert!(!body.contains("minio-secret"));
+        }
+
+        // The stored client keeps it
+        let Some(Storage::S3Compatible {
+            secret_access_key, ..
+        }) = db.get_client(&wallet)?.storage
+        else {
+            panic!("Expected an S3-compatible storage");
+        };
+        assert_eq!(secret_access_key, "minio-secret");
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_update_client_api() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let cache = ThinData(BackendCache::new(offline_sts()));
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .service(update_client);
+        let server = test::init_service(app).await;
+
+        // Insert to DB
+        let wallet = address!("0xc0ffee1234567890123456789012345678901234");
+        let client = Client::new(String::from("platformA"), wallet);
+        db.set_client(client.id.unwrap(), client.clone())?;
+
+        // Request & Response
+        let storage = Storage::S3 {
+            role_arn: String::from("arn:aws:iam::123456789012:role/xByte"),
+            region: String::from("us-east-1"),
+        };
+        let payload = UpdateClientRequest {
+            name: Some(String::from("platformB")),
+            storage: Some(storage.clone()),
+        };
+        let url = format!("/client/{}", client.id.as_ref().unwrap());
+        let req = test::TestRequest::patch()
+            .uri(&url)
+            .set_json(&payload)
+            .to_request();
+        let res: ResultAPI<Client, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        let data = db.get_client(&client.id.unwrap())?;
+        assert_eq!(data.name, "platformB");
+        assert_eq!(data.wallet, wallet);
+        assert_eq!(data.storage, Some(storage));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_delete_client_api() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let cache = ThinData(BackendCache::new(offline_sts()));
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .service(delete_client);
+        let server = test::init_service(app).await;
+
+        // Insert to DB
+        let wallet = address!("0xc0ffee1234567890123456789012345678901234");
+        let client = Client::new(String::from("platformA"), wallet);
+        db.set_client(client.id.unwrap(), client.clone())?;
+        db.assign_bucket(String::from("bucket"), client.id.unwrap())?;
+
+        // Request & Response
+        let url = format!("/client/{}", client.id.as_ref().unwrap());
+        let req = test::TestRequest::delete().uri(&url).to_request();
+        let res: ResultAPI<Client, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        assert!(db.get_client(&client.id.unwrap()).is_err());
+        assert!(db.get_bucket(&String::from("bucket")).is_err());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_list_clients_api() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let app = App::new().app_data(db.clone()).service(list_clients);
+        let server = test::init_service(app).await;
+
+        // Insert to DB
+        for i in 0..3u8 {
+            let client = Client::new(format!("platform{i}"), Address::repeat_byte(i));
+            db.set_client(client.id.unwrap(), client)?;
+        }
+
+        // Request & Response
+        let req = test::TestRequest::get()
+            .uri("/client?offset=1&limit=1")
+            .to_request();
+        let res: ResultAPI<ClientPage, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        let page = res.get_data().unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.clients.len(), 1);
+        assert_eq!(page.clients[0].wallet, Address::repeat_byte(1));
+        assert_eq!(page.next_offset, Some(2));
+        Ok(())
+    }
 }
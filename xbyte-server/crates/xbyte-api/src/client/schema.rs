@@ -1,23 +1,30 @@
 use alloy_primitives::Address;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use xbyte_evm::Factory;
 
 /// xByte Client
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Client<N = String> {
     /// Unique ID
+    #[schema(value_type = Option<String>)]
     pub id: Option<Address>,
     /// Nickname
     pub name: N,
     /// Wallet Address
+    #[schema(value_type = String)]
     pub wallet: Address,
     /// Vault Address
+    #[schema(value_type = Option<String>)]
     pub vault: Option<Address>,
     /// Data Storage Information
     pub storage: Option<Storage<N>>,
 }
 
+/// The placeholder of a secret returned by the API
+const REDACTED: &str = "<redacted>";
+
 impl<N> Client<N> {
     /// Create a new client
     pub fn new(name: N, wallet: Address) -> Self {
@@ -34,7 +41,17 @@ impl<N> Client<N> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+impl Client<String> {
+    /// The client without the secrets of its storage, as returned by the API
+    pub fn redacted(self) -> Self {
+        Self {
+            storage: self.storage.map(Storage::redacted),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum Storage<S> {
     /// AWS S3
@@ -44,20 +61,85 @@ pub enum Storage<S> {
         /// Region of the AWS account
         region: S,
     },
+    /// S3-compatible storage (e.g. MinIO, Cloudflare R2, Wasabi)
+    S3Compatible {
+        /// URL of the S3-compatible endpoint
+        endpoint: S,
+        /// Region of the bucket, if the provider has any
+        region: S,
+        /// Static access key ID
+        access_key_id: S,
+        /// Static secret access key
+        secret_access_key: S,
+        /// Use path-style addressing (`endpoint/bucket/key`)
+        #[serde(default)]
+        path_style: bool,
+    },
+    /// Google Cloud Storage
+    Gcs {
+        /// ID of the project owning the buckets
+        project_id: S,
+        /// Service account key (JSON), anonymous access without it
+        service_account_key: Option<S>,
+        /// Custom endpoint (e.g. an emulator)
+        endpoint: Option<S>,
+    },
+    /// Azure Blob Storage
+    AzureBlob {
+        /// Name of the storage account
+        account: S,
+        /// Shared key of the storage account (base64)
+        access_key: Option<S>,
+        /// Shared access signature, used when no access key is set
+        sas_token: Option<S>,
+        /// Custom endpoint (e.g. Azurite)
+        endpoint: Option<S>,
+    },
+    /// Local filesystem, one subdirectory per bucket
+    Local {
+        /// Root directory on the server
+        root: S,
+    },
 }
 
-impl<S> Storage<S> {
-    /// Get the role ARN
-    pub fn role_arn(&self) -> &S {
+impl Storage<String> {
+    /// The storage with its secrets replaced by a placeholder
+    pub fn redacted(self) -> Self {
         match self {
-            Self::S3 { role_arn, .. } => role_arn,
-        }
-    }
-
-    /// Get the region
-    pub fn region(&self) -> &S {
-        match self {
-            Self::S3 { region, .. } => region,
+            Self::S3Compatible {
+                endpoint,
+                region,
+                access_key_id,
+                path_style,
+                ..
+            } => Self::S3Compatible {
+                endpoint,
+                region,
+                access_key_id,
+                secret_access_key: REDACTED.into(),
+                path_style,
+            },
+            Self::Gcs {
+                project_id,
+                service_account_key,
+                endpoint,
+            } => Self::Gcs {
+                project_id,
+                service_account_key: service_account_key.map(|_| REDACTED.into()),
+                endpoint,
+            },
+            Self::AzureBlob {
+                account,
+                access_key,
+                sas_token,
+                endpoint,
+            } => Self::AzureBlob {
+                account,
+                access_key: access_key.map(|_| REDACTED.into()),
+                sas_token: sas_token.map(|_| REDACTED.into()),
+                endpoint,
+            },
+            storage => storage,
         }
     }
 }
@@ -78,4 +160,49 @@ mod tests {
         assert!(client.vault.is_some());
         Ok(())
     }
+
+    #[test]
+    fn test_storage_s3_compatible() -> anyhow::Result<()> {
+        let json = r#"{"s3Compatible":{"endpoint":"http://localhost:9000","region":"auto","access_key_id":"minio","secret_access_key":"minio123","path_style":true}}"#;
+        let storage: Storage<String> = serde_json::from_str(json)?;
+
+        let Storage::S3Compatible {
+            region, path_style, ..
+        } = storage
+        else {
+            panic!("Expected an S3-compatible storage");
+        };
+        assert_eq!(region, "auto");
+        assert!(path_style);
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_redacted() {
+        let gcs = Storage::Gcs {
+            project_id: String::from("project"),
+            service_account_key: Some(String::from("{\"private_key\": \"secret\"}")),
+            endpoint: None,
+        };
+        let azure = Storage::AzureBlob {
+            account: String::from("account"),
+            access_key: None,
+            sas_token: Some(String::from("sv=2024&sig=secret")),
+            endpoint: None,
+        };
+
+        // Set secrets are replaced, unset ones stay unset
+        for storage in [gcs, azure] {
+            let json = serde_json::to_string(&storage.redacted()).unwrap();
+            assert!(!json.contains("secret"));
+            assert!(json.contains(REDACTED));
+        }
+        let azure = Storage::AzureBlob {
+            account: String::from("account"),
+            access_key: None,
+            sas_token: None,
+            endpoint: None,
+        };
+        assert_eq!(azure.clone().redacted(), azure);
+    }
 }
@@ -0,0 +1,648 @@
+use crate::ConfigX402;
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The default address to bind the server to
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+/// The default log filter
+const DEFAULT_LOG_FILTER: &str = "xbyte_api=debug,xbyte_app=debug";
+/// The default interval between database snapshots
+const DEFAULT_SNAPSHOT_INTERVAL: u64 = 30;
+/// The default object requests per minute of a client IP
+const DEFAULT_RATE_LIMIT_IP: u32 = 600;
+/// The default paid requests per minute of a payer
+const DEFAULT_RATE_LIMIT_PAYER: u32 = 300;
+/// The default invalid payments per minute of a client IP
+const DEFAULT_RATE_LIMIT_INVALID_PAYMENT: u32 = 10;
+/// The default seconds given to responses and settlements on shutdown
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+/// The configuration of the API server, read from a TOML file with environment overrides
+///
+/// | Key                          | Environment                  | Default             |
+/// |------------------------------|------------------------------|---------------------|
+/// | `addr`                       | `SERVER_ADDR`                | `127.0.0.1:8080`    |
+/// | `rpc_url`                    | `RPC_URL`                    | required            |
+/// | `workers`                    | `SERVER_WORKERS`             | one per CPU core    |
+/// | `shutdown_timeout`           | `SHUTDOWN_TIMEOUT`           | `30` seconds        |
+/// | `local_root`                 | `LOCAL_STORAGE_ROOT`         | local storage off   |
+/// | `storage_endpoints`          | `STORAGE_ENDPOINTS`          | none                |
+/// | `tls.cert_path`              | `TLS_CERT_PATH`              | plain HTTP          |
+/// | `tls.key_path`               | `TLS_KEY_PATH`               | plain HTTP          |
+/// | `cors.allowed_origins`       | `CORS_ALLOWED_ORIGINS`       | none                |
+/// | `database.path`              | `DATABASE_PATH`              | in-memory only      |
+/// | `x402.network`               | `X402_NETWORK`               | `base-sepolia`      |
+/// | `x402.token`                 | `X402_TOKEN`                 | USDC on the network |
+/// | `x402.payment_address`       | `X402_PAYMENT_ADDRESS`       | the xByte treasury  |
+/// | `aws.region`                 | `AWS_REGION`                 | the AWS defaults    |
+/// | `aws.profile`                | `AWS_PROFILE`                | the AWS defaults    |
+/// | `log.format`                 | `LOG_FORMAT`                 | `text`              |
+/// | `log.filter`                 | `RUST_LOG`                   | xByte crates, debug |
+/// | `rate_limit.per_ip`          | `RATE_LIMIT_PER_IP`          | `600` per minute    |
+/// | `rate_limit.per_payer`       | `RATE_LIMIT_PER_PAYER`       | `300` per minute    |
+/// | `rate_limit.invalid_payment` | `RATE_LIMIT_INVALID_PAYMENT` | `10` per minute     |
+/// | `rate_limit.trust_proxy`     | `RATE_LIMIT_TRUST_PROXY`     | `false`             |
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The address to bind the server to
+    pub addr: String,
+    /// The RPC URL (e.g. ethereum, base, etc.)
+    pub rpc_url: String,
+    /// The number of HTTP workers
+    pub workers: Option<usize>,
+    /// The seconds in-flight responses, then pending settlements, get to finish on shutdown
+    pub shutdown_timeout: u64,
+    /// The directory local storages are allowed in
+    pub local_root: Option<PathBuf>,
+    /// The origins storages may use as custom endpoints, e.g. MinIO or an emulator
+    pub storage_endpoints: Vec<String>,
+    /// The certificate to terminate TLS with, plain HTTP when unset
+    pub tls: Option<TlsConfig>,
+    /// The CORS policy of the admin routes
+    pub cors: CorsConfig,
+    /// The x402 payment requirements
+    pub x402: X402Config,
+    /// The database backend
+    pub database: DatabaseConfig,
+    /// The AWS SDK settings, used for STS
+    pub aws: AwsConfig,
+    /// The log output
+    pub log: LogConfig,
+    /// The rate limits of the object routes
+    pub rate_limit: RateLimitConfig,
+}
+
+/// The CORS policy of the server, the paid object and quote routes allow any origin
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// The origins allowed to call the client, pricing and registration routes
+    pub allowed_origins: Vec<String>,
+}
+
+/// The TLS certificate of the server, reloaded when its files change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// The PEM certificate chain, leaf first
+    pub cert_path: PathBuf,
+    /// The PEM private key of the leaf certificate
+    pub key_path: PathBuf,
+}
+
+/// The x402 payment requirements advertised in challenges
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct X402Config {
+    /// The payment scheme
+    pub scheme: String,
+    /// The network payments are made on
+    pub network: String,
+    /// The token contract payments are made in
+    pub token: String,
+    /// The address receiving the platform share
+    pub payment_address: String,
+    /// The mime type of the paid resource
+    pub mime_type: String,
+    /// Extra scheme parameters, e.g. the EIP-712 domain of the token
+    pub extra: HashMap<String, String>,
+}
+
+/// The database backend of the server
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DatabaseConfig {
+    /// Keep everything in memory, lost on restart
+    #[default]
+    Memory,
+    /// Keep everything in memory, snapshotted to a JSON file
+    File {
+        /// The snapshot file
+        path: PathBuf,
+        /// The seconds between snapshots
+        #[serde(default = "default_snapshot_interval")]
+        snapshot_interval: u64,
+    },
+}
+
+/// The AWS SDK settings, falling back to the SDK defaults when unset
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AwsConfig {
+    /// The region of the STS client
+    pub region: Option<String>,
+    /// The shared config profile to load credentials from
+    pub profile: Option<String>,
+}
+
+/// The log output of the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// The format of log lines
+    pub format: LogFormat,
+    /// The `tracing` filter directives
+    pub filter: String,
+}
+
+/// The rate limits of the object routes, in requests per minute where 0 disables the limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The object requests of a client IP
+    pub per_ip: u32,
+    /// The paid requests of a payer
+    pub per_payer: u32,
+    /// The undecodable or rejected payments of a client IP, stricter as each costs a lookup
+    pub invalid_payment: u32,
+    /// Read the client IP from `Forwarded` or `X-Forwarded-For`, only set behind a proxy
+    pub trust_proxy: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_ip: DEFAULT_RATE_LIMIT_IP,
+            per_payer: DEFAULT_RATE_LIMIT_PAYER,
+            invalid_payment: DEFAULT_RATE_LIMIT_INVALID_PAYMENT,
+            trust_proxy: false,
+        }
+    }
+}
+
+/// The format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of the request span
+    Json,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: Default::default(),
+            filter: DEFAULT_LOG_FILTER.to_string(),
+        }
+    }
+}
+
+fn default_snapshot_interval() -> u64 {
+    DEFAULT_SNAPSHOT_INTERVAL
+}
+
+/// Parse an environment override, recording an error when it is not a number
+fn parse_env<T: std::str::FromStr>(
+    var: impl Fn(&str) -> Option<String>,
+    key: &str,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    let value = var(key)?;
+    let parsed = value.trim().parse().ok();
+    if parsed.is_none() {
+        errors.push(format!("{key}: {value:?} is not a number"));
+    }
+    parsed
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: DEFAULT_ADDR.to_string(),
+            rpc_url: Default::default(),
+            workers: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            local_root: None,
+            storage_endpoints: Vec::new(),
+            tls: None,
+            cors: Default::default(),
+            x402: Default::default(),
+            database: Default::default(),
+            aws: Default::default(),
+            log: Default::default(),
+            rate_limit: Default::default(),
+        }
+    }
+}
+
+impl Default for X402Config {
+    fn default() -> Self {
+        let config = ConfigX402::build();
+        Self {
+            scheme: config.scheme.to_string(),
+            network: config.network.to_string(),
+            token: config.token.to_string(),
+            payment_address: config.payment_address.to_string(),
+            mime_type: config.mime_type.to_string(),
+            extra: config
+                .extra
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// The snapshot file, `None` for a database kept in memory only
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Memory => None,
+            Self::File { path, .. } => Some(path),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Load the configuration from an optional file, apply the environment and validate it
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let config = Self::read(path)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Read the configuration from an optional file and apply the environment, unvalidated
+    ///
+    /// Environment overrides that cannot be parsed are reported here rather than by `validate`.
+    pub fn read(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|key| std::env::var(key).ok())?;
+
+        Ok(config)
+    }
+
+    /// Read the configuration from a TOML file
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read config file {}: {e}", path.display()))?;
+
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("failed to parse config file {}: {e}", path.display()))
+    }
+
+    /// Override the configuration with the variables returned by `var`, ignoring empty ones
+    ///
+    /// Unparsable values keep the configured ones and are reported all at once.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        let var = |key: &str| var(key).filter(|v| !v.trim().is_empty());
+        let mut errors = Vec::new();
+
+        if let Some(addr) = var("SERVER_ADDR") {
+            self.addr = addr;
+        }
+        if let Some(rpc_url) = var("RPC_URL") {
+            self.rpc_url = rpc_url;
+        }
+        if let Some(workers) = parse_env(var, "SERVER_WORKERS", &mut errors) {
+            self.workers = Some(workers);
+        }
+        if let Some(timeout) = parse_env(var, "SHUTDOWN_TIMEOUT", &mut errors) {
+            self.shutdown_timeout = timeout;
+        }
+        if let Some(root) = var("LOCAL_STORAGE_ROOT") {
+            self.local_root = Some(root.into());
+        }
+        if let Some(endpoints) = var("STORAGE_ENDPOINTS") {
+            let endpoints = endpoints.split(',').map(|e| e.trim().to_string());
+            self.storage_endpoints = endpoints.filter(|e| !e.is_empty()).collect();
+        }
+        let (cert_path, key_path) = (var("TLS_CERT_PATH"), var("TLS_KEY_PATH"));
+        if cert_path.is_some() || key_path.is_some() {
+            // A missing half is reported by `validate`
+            let tls = self.tls.get_or_insert_with(Default::default);
+            if let Some(path) = cert_path {
+                tls.cert_path = path.into();
+            }
+            if let Some(path) = key_path {
+                tls.key_path = path.into();
+            }
+        }
+        if let Some(origins) = var("CORS_ALLOWED_ORIGINS") {
+            let origins = origins.split(',').map(|o| o.trim().to_string());
+            self.cors.allowed_origins = origins.filter(|o| !o.is_empty()).collect();
+        }
+        if let Some(path) = var("DATABASE_PATH") {
+            let snapshot_interval = match self.database {
+                DatabaseConfig::File {
+                    snapshot_interval, ..
+                } => snapshot_interval,
+                DatabaseConfig::Memory => DEFAULT_SNAPSHOT_INTERVAL,
+            };
+            self.database = DatabaseConfig::File {
+                path: path.into(),
+                snapshot_interval,
+            };
+        }
+        if let Some(network) = var("X402_NETWORK") {
+            self.x402.network = network;
+        }
+        if let Some(token) = var("X402_TOKEN") {
+            self.x402.token = token;
+        }
+        if let Some(address) = var("X402_PAYMENT_ADDRESS") {
+            self.x402.payment_address = address;
+        }
+        if let Some(region) = var("AWS_REGION") {
+            self.aws.region = Some(region);
+        }
+        if let Some(profile) = var("AWS_PROFILE") {
+            self.aws.profile = Some(profile);
+        }
+        if let Some(format) = var("LOG_FORMAT") {
+            match format.to_lowercase().as_str() {
+                "text" => self.log.format = LogFormat::Text,
+                "json" => self.log.format = LogFormat::Json,
+                _ => errors.push(format!("LOG_FORMAT: {format:?} is not text or json")),
+            }
+        }
+        if let Some(filter) = var("RUST_LOG") {
+            self.log.filter = filter;
+        }
+        if let Some(per_ip) = parse_env(var, "RATE_LIMIT_PER_IP", &mut errors) {
+            self.rate_limit.per_ip = per_ip;
+        }
+        if let Some(per_payer) = parse_env(var, "RATE_LIMIT_PER_PAYER", &mut errors) {
+            self.rate_limit.per_payer = per_payer;
+        }
+        if let Some(invalid_payment) = parse_env(var, "RATE_LIMIT_INVALID_PAYMENT", &mut errors) {
+            self.rate_limit.invalid_payment = invalid_payment;
+        }
+        if let Some(trust_proxy) = var("RATE_LIMIT_TRUST_PROXY") {
+            match trust_proxy.trim() {
+                "1" | "true" => self.rate_limit.trust_proxy = true,
+                "0" | "false" => self.rate_limit.trust_proxy = false,
+                _ => errors.push(format!(
+                    "RATE_LIMIT_TRUST_PROXY: {trust_proxy:?} is not true or false"
+                )),
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!(
+                "invalid environment overrides:\n  - {}",
+                errors.join("\n  - ")
+            )),
+        }
+    }
+
+    /// Check the configuration, reporting every problem at once
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+
+        if self.addr.to_socket_addrs().is_err() {
+            errors.push(format!(
+                "addr: {:?} is not a socket address, e.g. 0.0.0.0:8080",
+                self.addr
+            ));
+        }
+        if self.rpc_url.is_empty() {
+            errors.push(String::from(
+                "rpc_url: missing, set it in the config file or with RPC_URL",
+            ));
+        } else if let Err(e) = Url::parse(&self.rpc_url) {
+            errors.push(format!("rpc_url: {:?} is not a URL ({e})", self.rpc_url));
+        }
+        if self.workers == Some(0) {
+            errors.push(String::from("workers: must be a positive number"));
+        }
+        if let Some(root) = &self.local_root
+            && !root.is_dir()
+        {
+            errors.push(format!("local_root: {} is not a directory", root.display()));
+        }
+        for endpoint in &self.storage_endpoints {
+            match Url::parse(endpoint) {
+                Ok(url) if url.origin().is_tuple() => {}
+                _ => errors.push(format!(
+                    "storage_endpoints: {endpoint:?} is not an origin, e.g. http://127.0.0.1:9000"
+                )),
+            }
+        }
+        if let Some(tls) = &self.tls {
+            for (key, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+                if !path.is_file() {
+                    errors.push(format!("tls.{key}: {} is not a file", path.display()));
+                }
+            }
+        }
+        for origin in &self.cors.allowed_origins {
+            match Url::parse(origin) {
+                Ok(url) if url.origin().is_tuple() => {}
+                _ => errors.push(format!(
+                    "cors.allowed_origins: {origin:?} is not an origin, e.g. https://xbyte.sh"
+                )),
+            }
+        }
+        if self.x402.token.parse::<Address>().is_err() {
+            errors.push(format!(
+                "x402.token: {:?} is not an address",
+                self.x402.token
+            ));
+        }
+        if self.x402.payment_address.parse::<Address>().is_err() {
+            errors.push(format!(
+                "x402.payment_address: {:?} is not an address",
+                self.x402.payment_address
+            ));
+        }
+        if let DatabaseConfig::File {
+            path,
+            snapshot_interval,
+        } = &self.database
+        {
+            let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+            if parent.is_some_and(|p| !p.is_dir()) {
+                errors.push(format!(
+                    "database.path: the directory of {} does not exist",
+                    path.display()
+                ));
+            }
+            if *snapshot_interval == 0 {
+                errors.push(String::from(
+                    "database.snapshot_interval: must be a positive number of seconds",
+                ));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!(
+                "invalid server configuration:\n  - {}",
+                errors.join("\n  - ")
+            )),
+        }
+    }
+}
+
+impl From<&X402Config> for ConfigX402<&'static str> {
+    /// The challenges borrow the configuration for the lifetime of the server, so it is leaked once at startup
+    fn from(config: &X402Config) -> Self {
+        let leak = |s: &String| -> &'static str { s.clone().leak() };
+
+        Self {
+            scheme: leak(&config.scheme),
+            payment_address: leak(&config.payment_address),
+            token: leak(&config.token),
+            network: leak(&config.network),
+            mime_type: leak(&config.mime_type),
+            extra: config
+                .extra
+                .iter()
+                .map(|(k, v)| (leak(k), leak(v)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        addr = "0.0.0.0:8080"
+        rpc_url = "https://sepolia.base.org"
+        workers = 4
+
+        [cors]
+        allowed_origins = ["https://xbyte.sh"]
+
+        [x402]
+        network = "base"
+
+        [database]
+        kind = "file"
+        path = "xbyte.json"
+    "#;
+
+    #[test]
+    fn test_parse_config() -> anyhow::Result<()> {
+        let config: ServerConfig = toml::from_str(CONFIG)?;
+
+        assert_eq!(config.workers, Some(4));
+        assert_eq!(config.cors.allowed_origins, ["https://xbyte.sh"]);
+        assert_eq!(config.x402.network, "base");
+        assert_eq!(config.x402.scheme, "exact");
+        assert_eq!(
+            config.database,
+            DatabaseConfig::File {
+                path: PathBuf::from("xbyte.json"),
+                snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            }
+        );
+        config.validate()?;
+
+        // Typos are rejected rather than silently ignored
+        assert!(toml::from_str::<ServerConfig>("worker = 4").is_err());
+
+        // The shipped example stays valid
+        let example: ServerConfig = toml::from_str(include_str!("../../../xbyte.example.toml"))?;
+        assert_eq!(example.x402, X402Config::default());
+        example.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_overrides() -> anyhow::Result<()> {
+        let env = HashMap::from([
+            ("RPC_URL", "http://localhost:8545"),
+            ("SERVER_WORKERS", "2"),
+            ("SHUTDOWN_TIMEOUT", "5"),
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://a.xbyte.sh, https://b.xbyte.sh",
+            ),
+            ("X402_NETWORK", ""),
+            ("LOG_FORMAT", "JSON"),
+            ("RATE_LIMIT_PER_IP", "60"),
+            ("RATE_LIMIT_TRUST_PROXY", "true"),
+            ("TLS_CERT_PATH", "/etc/xbyte/cert.pem"),
+        ]);
+        let mut config = ServerConfig::default();
+        config.apply_env(|key| env.get(key).map(|v| v.to_string()))?;
+
+        assert_eq!(config.rpc_url, "http://localhost:8545");
+        assert_eq!(config.workers, Some(2));
+        assert_eq!(config.shutdown_timeout, 5);
+        assert_eq!(config.cors.allowed_origins.len(), 2);
+        assert_eq!(config.x402.network, "base-sepolia");
+        assert_eq!(config.addr, DEFAULT_ADDR);
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.rate_limit.per_ip, 60);
+        assert_eq!(config.rate_limit.per_payer, DEFAULT_RATE_LIMIT_PAYER);
+        assert!(config.rate_limit.trust_proxy);
+        let tls = config.tls.unwrap();
+        assert_eq!(tls.cert_path, PathBuf::from("/etc/xbyte/cert.pem"));
+        assert_eq!(tls.key_path, PathBuf::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_env_overrides() {
+        let env = HashMap::from([
+            ("RPC_URL", "http://localhost:8545"),
+            ("SERVER_WORKERS", "four"),
+            ("SHUTDOWN_TIMEOUT", "30s"),
+            ("LOG_FORMAT", "yaml"),
+            ("RATE_LIMIT_PER_IP", "-1"),
+            ("RATE_LIMIT_PER_PAYER", "300/min"),
+            ("RATE_LIMIT_INVALID_PAYMENT", "ten"),
+            ("RATE_LIMIT_TRUST_PROXY", "yes"),
+        ]);
+        let mut config = ServerConfig::default();
+        let error = config
+            .apply_env(|key| env.get(key).map(|v| v.to_string()))
+            .unwrap_err()
+            .to_string();
+
+        // The configured values are kept, but the typos are not silently ignored
+        assert_eq!(config.rate_limit, RateLimitConfig::default());
+        assert_eq!(config.shutdown_timeout, DEFAULT_SHUTDOWN_TIMEOUT);
+        for key in env.keys().filter(|k| **k != "RPC_URL") {
+            assert!(error.contains(&format!("  - {key}:")), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ServerConfig {
+            addr: String::from("localhost"),
+            workers: Some(0),
+            cors: CorsConfig {
+                allowed_origins: vec![String::from("xbyte.sh")],
+            },
+            storage_endpoints: vec![String::from("minio:9000")],
+            tls: Some(Default::default()),
+            ..Default::default()
+        };
+
+        // Every problem is reported
+        let error = config.validate().unwrap_err().to_string();
+        let keys = ["addr", "rpc_url", "workers", "cors.allowed_origins"];
+        let keys = keys.into_iter().chain(["storage_endpoints"]);
+        for key in keys.chain(["tls.cert_path", "tls.key_path"]) {
+            assert!(error.contains(&format!("  - {key}:")), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_config_x402() {
+        let config = ConfigX402::from(&X402Config::default());
+        let default = ConfigX402::build();
+
+        assert_eq!(config.token, default.token);
+        assert_eq!(config.extra, default.extra);
+    }
+}
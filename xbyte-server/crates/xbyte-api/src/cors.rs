@@ -0,0 +1,158 @@
+use crate::config::CorsConfig;
+use crate::s3::{OBJECT_SIZE_HEADER, OBJECT_TYPE_HEADER, PRICE_HEADER, TOTAL_PRICE_HEADER};
+use crate::trace::REQUEST_ID_HEADER;
+use crate::version::DEPRECATION_HEADER;
+use crate::x402::{PAYMENT_HEADER, PAYMENT_RESPONSE_HEADER};
+use actix_cors::Cors;
+use actix_web::dev::ResourceDef;
+use actix_web::http::header::{ACCEPT, CONTENT_RANGE, CONTENT_TYPE, LINK};
+
+/// The routes players embed, open to any origin
+///
+/// The paid object routes serve the content and the price lookup is the quote shown before
+/// paying. Every other route (clients, price updates, bucket registration) is limited to the
+/// configured origins. The unversioned aliases are matched as well.
+const PUBLIC_ROUTES: [&str; 6] = [
+    "/v1/s3/bucket/{bucket}/object/{object}",
+    "/v1/s3/bucket/{bucket}/object/{object}/info",
+    "/v1/price/{bucket}/{object}",
+    "/s3/bucket/{bucket}/object/{object}",
+    "/s3/bucket/{bucket}/object/{object}/info",
+    "/price/{bucket}/{object}",
+];
+
+/// The seconds browsers may cache a preflight response
+const MAX_AGE: usize = 3600;
+
+/// Build the CORS middleware, public routes allow any origin and the rest only the allow-list
+pub(crate) fn cors(config: &CorsConfig) -> Cors {
+    let public = ResourceDef::new(PUBLIC_ROUTES.to_vec());
+
+    let cors = Cors::default()
+        .allowed_origin_fn(move |_, head| public.is_match(head.uri.path()))
+        .allowed_methods(["GET", "HEAD", "POST", "PATCH", "DELETE"])
+        .allowed_headers([
+            ACCEPT.as_str(),
+            CONTENT_TYPE.as_str(),
+            PAYMENT_HEADER,
+            REQUEST_ID_HEADER.as_str(),
+        ])
+        .expose_headers([
+            PAYMENT_RESPONSE_HEADER,
+            REQUEST_ID_HEADER.as_str(),
+            CONTENT_RANGE.as_str(),
+            OBJECT_SIZE_HEADER,
+            OBJECT_TYPE_HEADER,
+            PRICE_HEADER,
+            TOTAL_PRICE_HEADER,
+            DEPRECATION_HEADER.as_str(),
+            LINK.as_str(),
+        ])
+        .max_age(MAX_AGE);
+
+    config
+        .allowed_origins
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    };
+    use actix_web::{App, HttpResponse, test, web};
+
+    const PLATFORM: &str = "https://platform.xbyte.sh";
+    const PLAYER: &str = "https://player.example.com";
+
+    #[actix_web::test]
+    async fn test_route_groups() {
+        let config = CorsConfig {
+            allowed_origins: vec![PLATFORM.to_string()],
+        };
+        let app = App::new()
+            .default_service(web::to(HttpResponse::Ok))
+            .wrap(cors(&config));
+        let app = test::init_service(app).await;
+
+        // Players may fetch paid objects and quotes from anywhere
+        for uri in [
+            "/v1/s3/bucket/media/object/song.mp3",
+            "/v1/price/media/song.mp3",
+            "/s3/bucket/media/object/song.mp3",
+            "/price/media/song.mp3",
+        ] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((ORIGIN, PLAYER))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(
+                res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+                PLAYER
+            );
+            let exposed = res.headers().get(ACCESS_CONTROL_EXPOSE_HEADERS).unwrap();
+            let exposed = exposed.to_str().unwrap().to_lowercase();
+            assert!(exposed.contains(&PAYMENT_RESPONSE_HEADER.to_lowercase()));
+        }
+
+        // Paid requests may carry the payment header
+        let req = test::TestRequest::default()
+            .method(actix_web::http::Method::OPTIONS)
+            .uri("/s3/bucket/media/object/song.mp3")
+            .insert_header((ORIGIN, PLAYER))
+            .insert_header((ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .insert_header((ACCESS_CONTROL_REQUEST_HEADERS, "x-payment"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.status().is_success());
+
+        // Admin routes are limited to the allow-list
+        for uri in [
+            "/v1/client",
+            "/v1/price",
+            "/v1/s3/register",
+            "/client",
+            "/s3/bucket",
+        ] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((ORIGIN, PLAYER))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert!(
+                res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none(),
+                "{uri}"
+            );
+
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((ORIGIN, PLATFORM))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(
+                res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+                PLATFORM
+            );
+        }
+
+        // The platform may update clients, which the routes do with PATCH rather than PUT
+        let preflight = |method| {
+            test::TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/v1/client/0x1234567890123456789012345678901234567890")
+                .insert_header((ORIGIN, PLATFORM))
+                .insert_header((ACCESS_CONTROL_REQUEST_METHOD, method))
+                .to_request()
+        };
+        let res = test::call_service(&app, preflight("PATCH")).await;
+        assert!(res.status().is_success());
+        let methods = res.headers().get(ACCESS_CONTROL_ALLOW_METHODS).unwrap();
+        assert!(methods.to_str().unwrap().contains("PATCH"));
+        let res = test::call_service(&app, preflight("PUT")).await;
+        assert!(!res.status().is_success());
+    }
+}
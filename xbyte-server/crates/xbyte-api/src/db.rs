@@ -1,6 +1,8 @@
-use crate::{Client, Storage};
+use crate::{Client, Payment, Storage};
 use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 
 /// A trait for a database
@@ -19,6 +21,10 @@ pub trait Database {
     type Bucket;
     /// The storage type
     type Storage;
+    /// The payment key type
+    type KeyPayment;
+    /// The payment type
+    type Payment;
 
     /// Set the price
     fn set_price(&self, key: Self::KeyPrice, price: Self::Price) -> anyhow::Result<()>;
@@ -30,24 +36,138 @@ pub trait Database {
     fn get_client(&self, key: &Self::KeyClient) -> anyhow::Result<Self::Client>;
     /// Get all clients
     fn get_all_clients(&self) -> anyhow::Result<Vec<Self::Client>>;
+    /// Delete client along with its buckets, prices and storage
+    fn delete_client(&self, key: &Self::KeyClient) -> anyhow::Result<Self::Client>;
     /// Assign Bucket to Client
     fn assign_bucket(&self, key: Self::KeyBucket, client: Self::KeyClient) -> anyhow::Result<()>;
     /// Get Bucket from Client
     fn get_bucket(&self, key: &Self::KeyBucket) -> anyhow::Result<Self::Bucket>;
+    /// Remove Bucket along with its prices
+    fn remove_bucket(&self, key: &Self::KeyBucket) -> anyhow::Result<Self::Bucket>;
+    /// Get all Buckets of a Client
+    fn get_client_buckets(&self, client: &Self::KeyClient) -> anyhow::Result<Vec<Self::KeyBucket>>;
     /// Assign Storage to Client
     fn assign_storage(&self, key: Self::KeyClient, storage: Self::Storage) -> anyhow::Result<()>;
     /// Get Storage from Client
     fn get_storage(&self, key: &Self::KeyClient) -> anyhow::Result<Self::Storage>;
     /// Get all storages
     fn get_all_storages(&self) -> anyhow::Result<Vec<Self::Storage>>;
+    /// Record a payment, replacing any previous record
+    fn record_payment(&self, key: Self::KeyPayment, payment: Self::Payment) -> anyhow::Result<()>;
+    /// Get a payment
+    fn get_payment(&self, key: &Self::KeyPayment) -> anyhow::Result<Self::Payment>;
+    /// Get all payments along with their keys
+    fn get_all_payments(&self) -> anyhow::Result<Vec<(Self::KeyPayment, Self::Payment)>>;
+    /// Check that the database is usable
+    fn ping(&self) -> anyhow::Result<()>;
 }
 
 /// In-memory database
+///
+/// Methods taking several locks take them in the order of the fields below.
 #[derive(Debug, Default, Clone)]
 pub struct MemoryDB {
-    prices: Arc<RwLock<HashMap<(String, String), u64>>>,
     clients: Arc<RwLock<HashMap<Address, Client>>>,
     buckets: Arc<RwLock<HashMap<String, Address>>>,
+    prices: Arc<RwLock<HashMap<(String, String), u64>>>,
+    payments: Arc<RwLock<HashMap<String, Payment>>>,
+}
+
+/// The version of the snapshot format
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// A serializable copy of the in-memory database
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    prices: Vec<(String, String, u64)>,
+    clients: Vec<(Address, Client)>,
+    buckets: HashMap<String, Address>,
+    payments: HashMap<String, Payment>,
+}
+
+impl MemoryDB {
+    /// Load a database from a snapshot file, empty if the file does not exist yet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read(path)?;
+        let snapshot: Snapshot = serde_json::from_slice(&content)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow::anyhow!(
+                "unsupported snapshot version {}",
+                snapshot.version
+            ));
+        }
+
+        let prices = snapshot.prices.into_iter().map(|(b, o, p)| ((b, o), p));
+        Ok(Self {
+            prices: Arc::new(RwLock::new(prices.collect())),
+            clients: Arc::new(RwLock::new(snapshot.clients.into_iter().collect())),
+            buckets: Arc::new(RwLock::new(snapshot.buckets)),
+            payments: Arc::new(RwLock::new(snapshot.payments)),
+        })
+    }
+
+    /// Upgrade a snapshot file to the current format, creating an empty one if missing
+    ///
+    /// Returns the version the snapshot was in, `None` when it was created.
+    pub fn migrate(path: &Path) -> anyhow::Result<Option<u32>> {
+        /// The part of a snapshot every version has
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+
+        if !path.exists() {
+            Self::default().save(path)?;
+            return Ok(None);
+        }
+
+        let Header { version } = serde_json::from_slice(&std::fs::read(path)?)?;
+        match version {
+            SNAPSHOT_VERSION => {}
+            v if v > SNAPSHOT_VERSION => {
+                return Err(anyhow::anyhow!(
+                    "snapshot version {v} is newer than the supported {SNAPSHOT_VERSION}"
+                ));
+            }
+            v => return Err(anyhow::anyhow!("no migration from snapshot version {v}")),
+        }
+
+        // Rewrite the snapshot, filling in fields added since it was written
+        Self::load(path)?.save(path)?;
+        Ok(Some(version))
+    }
+
+    /// Write a snapshot of the database, replacing the file atomically
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let snapshot = {
+            // Locked in the same order as every other method to avoid deadlocks
+            let clients = self.clients.read().unwrap();
+            let buckets = self.buckets.read().unwrap();
+            let prices = self.prices.read().unwrap();
+            let payments = self.payments.read().unwrap();
+            Snapshot {
+                version: SNAPSHOT_VERSION,
+                prices: prices
+                    .iter()
+                    .map(|((b, o), p)| (b.clone(), o.clone(), *p))
+                    .collect(),
+                clients: clients.iter().map(|(k, c)| (*k, c.clone())).collect(),
+                buckets: buckets.clone(),
+                payments: payments.clone(),
+            }
+        };
+
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&snapshot)?)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
 }
 
 impl Database for MemoryDB {
@@ -58,6 +178,8 @@ impl Database for MemoryDB {
     type KeyBucket = String;
     type Bucket = Address;
     type Storage = Storage<String>;
+    type KeyPayment = String;
+    type Payment = Payment;
 
     fn set_price(&self, key: Self::KeyPrice, price: Self::Price) -> anyhow::Result<()> {
         // Set the price
@@ -94,6 +216,28 @@ impl Database for MemoryDB {
         Ok(result)
     }
 
+    fn delete_client(&self, key: &Self::KeyClient) -> anyhow::Result<Self::Client> {
+        let mut clients = self.clients.write().unwrap();
+        let mut buckets = self.buckets.write().unwrap();
+        let mut prices = self.prices.write().unwrap();
+
+        // Remove the client, dropping its storage with it
+        let client = clients
+            .remove(key)
+            .ok_or(anyhow::anyhow!("Client not found"))?;
+
+        // Cascade to the client's buckets and their prices
+        buckets.retain(|bucket, owner| {
+            let owned = owner == key;
+            if owned {
+                prices.retain(|(b, _), _| b != bucket);
+            }
+            !owned
+        });
+
+        Ok(client)
+    }
+
     fn assign_bucket(&self, key: Self::KeyBucket, client: Self::KeyClient) -> anyhow::Result<()> {
         let mut db = self.buckets.write().unwrap();
         db.insert(key, client);
@@ -108,6 +252,29 @@ impl Database for MemoryDB {
         Ok(*result)
     }
 
+    fn remove_bucket(&self, key: &Self::KeyBucket) -> anyhow::Result<Self::Bucket> {
+        let mut buckets = self.buckets.write().unwrap();
+        let mut prices = self.prices.write().unwrap();
+
+        let owner = buckets
+            .remove(key)
+            .ok_or(anyhow::anyhow!("Bucket not found"))?;
+        prices.retain(|(b, _), _| b != key);
+
+        Ok(owner)
+    }
+
+    fn get_client_buckets(&self, client: &Self::KeyClient) -> anyhow::Result<Vec<Self::KeyBucket>> {
+        let db = self.buckets.read().unwrap();
+        let result = db
+            .iter()
+            .filter(|(_, owner)| *owner == client)
+            .map(|(bucket, _)| bucket.clone())
+            .collect();
+
+        Ok(result)
+    }
+
     fn assign_storage(&self, key: Self::KeyClient, storage: Self::Storage) -> anyhow::Result<()> {
         let mut db = self.clients.write().unwrap();
         match db.get_mut(&key) {
@@ -134,11 +301,45 @@ impl Database for MemoryDB {
 
         Ok(result)
     }
+
+    fn record_payment(&self, key: Self::KeyPayment, payment: Self::Payment) -> anyhow::Result<()> {
+        let mut db = self.payments.write().unwrap();
+        db.insert(key, payment);
+
+        Ok(())
+    }
+
+    fn get_payment(&self, key: &Self::KeyPayment) -> anyhow::Result<Self::Payment> {
+        let db = self.payments.read().unwrap();
+        let result = db.get(key).ok_or(anyhow::anyhow!("Payment not found"))?;
+
+        Ok(result.clone())
+    }
+
+    fn get_all_payments(&self) -> anyhow::Result<Vec<(Self::KeyPayment, Self::Payment)>> {
+        let db = self.payments.read().unwrap();
+        let result = db.iter().map(|(k, p)| (k.clone(), p.clone())).collect();
+
+        Ok(result)
+    }
+
+    fn ping(&self) -> anyhow::Result<()> {
+        // A panic while holding a lock leaves the data in an unknown state
+        let poisoned = self.prices.is_poisoned()
+            || self.clients.is_poisoned()
+            || self.buckets.is_poisoned()
+            || self.payments.is_poisoned();
+        match poisoned {
+            true => Err(anyhow::anyhow!("Database lock poisoned")),
+            false => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::PaymentStatus;
     use alloy_primitives::address;
 
     const TEST_WALLET: Address = address!("1234567890123456789012345678901234567890");
@@ -200,4 +401,123 @@ mod tests {
         assert_eq!(storage_info, storage);
         Ok(())
     }
+
+    #[test]
+    fn test_delete_client_cascade() -> anyhow::Result<()> {
+        let db = MemoryDB::default();
+        let client = Client::new("test".to_string(), TEST_WALLET);
+        let other = Client::new("other".to_string(), Address::ZERO);
+        db.set_client(client.id.unwrap(), client.clone())?;
+        db.set_client(other.id.unwrap(), other.clone())?;
+
+        // Assign buckets and prices to both clients
+        db.assign_bucket("bucket_a".to_string(), client.id.unwrap())?;
+        db.assign_bucket("bucket_b".to_string(), other.id.unwrap())?;
+        db.set_price(("bucket_a".to_string(), "obj".to_string()), 10)?;
+        db.set_price(("bucket_b".to_string(), "obj".to_string()), 20)?;
+
+        let deleted = db.delete_client(&client.id.unwrap())?;
+        assert_eq!(deleted, client);
+
+        // Verify only the deleted client's data is gone
+        assert!(db.get_client(&client.id.unwrap()).is_err());
+        assert!(db.get_bucket(&"bucket_a".to_string()).is_err());
+        assert!(
+            db.get_price(&("bucket_a".to_string(), "obj".to_string()))
+                .is_err()
+        );
+        assert_eq!(db.get_bucket(&"bucket_b".to_string())?, other.id.unwrap());
+        assert_eq!(
+            db.get_price(&("bucket_b".to_string(), "obj".to_string()))?,
+            20
+        );
+        assert!(db.delete_client(&client.id.unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_bucket() -> anyhow::Result<()> {
+        let db = MemoryDB::default();
+        let bucket_key = String::from("test_bucket");
+        let price_key = (bucket_key.clone(), String::from("obj"));
+        db.assign_bucket(bucket_key.clone(), TEST_WALLET)?;
+        db.set_price(price_key.clone(), 10)?;
+
+        let owner = db.remove_bucket(&bucket_key)?;
+        assert_eq!(owner, TEST_WALLET);
+        assert!(db.get_bucket(&bucket_key).is_err());
+        assert!(db.get_price(&price_key).is_err());
+        assert!(db.remove_bucket(&bucket_key).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_payment() -> anyhow::Result<()> {
+        let db = MemoryDB::default();
+        let nonce = String::from("0x01");
+        let mut payment = Payment {
+            bucket: String::from("test_bucket"),
+            object: String::from("obj"),
+            payer: TEST_WALLET.to_string(),
+            amount: String::from("1000"),
+            status: PaymentStatus::Pending,
+            reason: None,
+            settlement: None,
+        };
+        db.record_payment(nonce.clone(), payment.clone())?;
+        assert_eq!(db.get_payment(&nonce)?, payment);
+        assert_eq!(db.get_all_payments()?, [(nonce.clone(), payment.clone())]);
+
+        // Void the payment
+        payment.status = PaymentStatus::Voided;
+        payment.reason = Some(String::from("delivery failed"));
+        db.record_payment(nonce.clone(), payment.clone())?;
+        assert_eq!(db.get_payment(&nonce)?.status, PaymentStatus::Voided);
+        assert!(db.get_payment(&String::from("0x02")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("xbyte-{}.json", uuid::Uuid::new_v4()));
+        assert!(MemoryDB::load(&path)?.get_all_clients()?.is_empty());
+
+        let db = MemoryDB::default();
+        let client = Client::new("test".to_string(), TEST_WALLET);
+        db.set_client(client.id.unwrap(), client.clone())?;
+        db.assign_bucket("test_bucket".to_string(), TEST_WALLET)?;
+        db.set_price(("test_bucket".to_string(), "obj".to_string()), 10)?;
+        db.save(&path)?;
+
+        // Verify the data
+        let db = MemoryDB::load(&path)?;
+        assert_eq!(db.get_client(&TEST_WALLET)?, client);
+        assert_eq!(db.get_bucket(&"test_bucket".to_string())?, TEST_WALLET);
+        assert_eq!(
+            db.get_price(&("test_bucket".to_string(), "obj".to_string()))?,
+            10
+        );
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("xbyte-{}.json", uuid::Uuid::new_v4()));
+
+        // A missing snapshot is created, an existing one is rewritten in place
+        assert_eq!(MemoryDB::migrate(&path)?, None);
+        assert!(MemoryDB::load(&path)?.get_all_clients()?.is_empty());
+        assert_eq!(MemoryDB::migrate(&path)?, Some(SNAPSHOT_VERSION));
+
+        // Snapshots of a newer server are left untouched
+        let newer = serde_json::json!({ "version": SNAPSHOT_VERSION + 1 });
+        std::fs::write(&path, newer.to_string())?;
+        assert!(MemoryDB::migrate(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, newer.to_string());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,366 @@
+use crate::ResultAPI;
+use crate::utils::ErrorStatus;
+use crate::x402::X402Response;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CONTENT_RANGE, RETRY_AFTER};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+/// The catalog of machine-readable error codes, which are stable across releases
+///
+/// | Code                        | Status | Retry | Meaning                                             |
+/// |-----------------------------|--------|-------|-----------------------------------------------------|
+/// | `invalid_request`           | 400    | no    | The body, query or path of the request is malformed |
+/// | `invalid_client_id`         | 400    | no    | The client ID is not a valid address                |
+/// | `no_buckets`                | 400    | no    | No buckets were given to register                   |
+/// | `bucket_not_accessible`     | 400    | no    | The storage credentials cannot access the bucket    |
+/// | `invalid_payment`           | 401    | no    | The `X-Payment` header cannot be decoded            |
+/// | `payment_required`          | 402    | no    | Pay the attached x402 challenge and retry           |
+/// | `bucket_not_owned`          | 403    | no    | The bucket is registered to another client          |
+/// | `client_not_found`          | 404    | no    | The client does not exist                           |
+/// | `bucket_not_found`          | 404    | no    | The bucket is not registered                        |
+/// | `bucket_owner_not_found`    | 404    | no    | The bucket owner has no storage or vault            |
+/// | `object_not_found`          | 404    | no    | The object does not exist in the bucket             |
+/// | `price_not_found`           | 404    | no    | No price is set for the object                      |
+/// | `client_exists`             | 409    | no    | A client with the same wallet already exists        |
+/// | `bucket_already_registered` | 409    | no    | The bucket is registered to another client          |
+/// | `range_not_satisfiable`     | 416    | no    | The range lies outside of the object                |
+/// | `rate_limited`              | 429    | yes   | Too many requests, wait `Retry-After` seconds       |
+/// | `internal_error`            | 500    | yes   | An unexpected server failure                        |
+/// | `storage_error`             | 502    | yes   | The storage provider failed or is unreachable       |
+/// | `facilitator_error`         | 502    | yes   | The x402 facilitator failed or is unreachable       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The body, query or path of the request is malformed
+    InvalidRequest,
+    /// The client ID is not a valid address
+    InvalidClientId,
+    /// No buckets were given to register
+    NoBuckets,
+    /// The storage credentials cannot access the bucket
+    BucketNotAccessible,
+    /// The `X-Payment` header cannot be decoded
+    InvalidPayment,
+    /// A payment is required to access the resource
+    PaymentRequired,
+    /// The bucket is registered to another client
+    BucketNotOwned,
+    /// The client does not exist
+    ClientNotFound,
+    /// The bucket is not registered
+    BucketNotFound,
+    /// The bucket owner has no storage or vault
+    BucketOwnerNotFound,
+    /// The object does not exist in the bucket
+    ObjectNotFound,
+    /// No price is set for the object
+    PriceNotFound,
+    /// A client with the same wallet already exists
+    ClientExists,
+    /// The bucket is registered to another client
+    BucketAlreadyRegistered,
+    /// The range lies outside of the object
+    RangeNotSatisfiable,
+    /// Too many requests from the client or payer
+    RateLimited,
+    /// An unexpected server failure
+    InternalError,
+    /// The storage provider failed or is unreachable
+    StorageError,
+    /// The x402 facilitator failed or is unreachable
+    FacilitatorError,
+}
+
+impl ErrorCode {
+    /// Every code of the catalog
+    pub const ALL: [Self; 19] = [
+        Self::InvalidRequest,
+        Self::InvalidClientId,
+        Self::NoBuckets,
+        Self::BucketNotAccessible,
+        Self::InvalidPayment,
+        Self::PaymentRequired,
+        Self::BucketNotOwned,
+        Self::ClientNotFound,
+        Self::BucketNotFound,
+        Self::BucketOwnerNotFound,
+        Self::ObjectNotFound,
+        Self::PriceNotFound,
+        Self::ClientExists,
+        Self::BucketAlreadyRegistered,
+        Self::RangeNotSatisfiable,
+        Self::RateLimited,
+        Self::InternalError,
+        Self::StorageError,
+        Self::FacilitatorError,
+    ];
+
+    /// The HTTP status of the code
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest
+            | Self::InvalidClientId
+            | Self::NoBuckets
+            | Self::BucketNotAccessible => StatusCode::BAD_REQUEST,
+            Self::InvalidPayment => StatusCode::UNAUTHORIZED,
+            Self::PaymentRequired => StatusCode::PAYMENT_REQUIRED,
+            Self::BucketNotOwned => StatusCode::FORBIDDEN,
+            Self::ClientNotFound
+            | Self::BucketNotFound
+            | Self::BucketOwnerNotFound
+            | Self::ObjectNotFound
+            | Self::PriceNotFound => StatusCode::NOT_FOUND,
+            Self::ClientExists | Self::BucketAlreadyRegistered => StatusCode::CONFLICT,
+            Self::RangeNotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::StorageError | Self::FacilitatorError => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// The default human-readable message of the code
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "Invalid request",
+            Self::InvalidClientId => "Invalid ID format",
+            Self::NoBuckets => "No buckets to register",
+            Self::BucketNotAccessible => "Bucket is not accessible",
+            Self::InvalidPayment => "Invalid X-Payment header",
+            Self::PaymentRequired => "Payment required",
+            Self::BucketNotOwned => "Bucket is owned by another client",
+            Self::ClientNotFound => "Client does not exist",
+            Self::BucketNotFound => "Bucket not found",
+            Self::BucketOwnerNotFound => "Bucket owner not found",
+            Self::ObjectNotFound => "Object not found",
+            Self::PriceNotFound => "Price not found",
+            Self::ClientExists => "Client already exists",
+            Self::BucketAlreadyRegistered => "Bucket already registered",
+            Self::RangeNotSatisfiable => "Range not satisfiable",
+            Self::RateLimited => "Too many requests",
+            Self::InternalError => "Internal server error",
+            Self::StorageError => "Storage provider failed",
+            Self::FacilitatorError => "Payment facilitator failed",
+        }
+    }
+
+    /// Whether retrying the same request may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited | Self::InternalError | Self::StorageError | Self::FacilitatorError
+        )
+    }
+}
+
+/// The machine-readable body of a failed API call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// The stable error code
+    pub code: ErrorCode,
+    /// The human-readable message
+    pub message: String,
+    /// Additional context, e.g. the offending bucket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ErrorStatus for ErrorBody {
+    fn status(&self) -> StatusCode {
+        self.code.status()
+    }
+}
+
+/// The errors returned by the API handlers
+#[derive(Debug)]
+pub enum ApiError {
+    /// A failure of the catalog
+    Failure {
+        /// The error code
+        code: ErrorCode,
+        /// The human-readable message
+        message: &'static str,
+        /// Additional context
+        details: Option<serde_json::Value>,
+    },
+    /// A payment is required to access the resource
+    PaymentRequired(Box<X402Response<&'static str, String>>),
+    /// The requested range lies outside of the object
+    RangeNotSatisfiable {
+        /// The size of the object
+        size: u64,
+    },
+    /// The client or payer exceeded a rate limit
+    RateLimited {
+        /// The seconds to wait before retrying
+        retry_after: u64,
+    },
+}
+
+impl ApiError {
+    /// Create a failure with a message specific to the call site
+    pub fn new(code: ErrorCode, message: &'static str) -> Self {
+        Self::Failure {
+            code,
+            message,
+            details: None,
+        }
+    }
+
+    /// Attach details to a failure
+    pub fn with_details(self, value: serde_json::Value) -> Self {
+        match self {
+            Self::Failure { code, message, .. } => Self::Failure {
+                code,
+                message,
+                details: Some(value),
+            },
+            other => other,
+        }
+    }
+
+    /// The machine-readable error code
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Failure { code, .. } => *code,
+            Self::PaymentRequired(_) => ErrorCode::PaymentRequired,
+            Self::RangeNotSatisfiable { .. } => ErrorCode::RangeNotSatisfiable,
+            Self::RateLimited { .. } => ErrorCode::RateLimited,
+        }
+    }
+
+    /// The human-readable message
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Failure { message, .. } => message,
+            other => other.code().message(),
+        }
+    }
+
+    /// The machine-readable body
+    pub fn body(&self) -> ErrorBody {
+        let details = match self {
+            Self::Failure { details, .. } => details.clone(),
+            Self::PaymentRequired(_) => None,
+            Self::RangeNotSatisfiable { size } => Some(serde_json::json!({ "size": size })),
+            Self::RateLimited { retry_after } => {
+                Some(serde_json::json!({ "retryAfter": retry_after }))
+            }
+        };
+
+        ErrorBody {
+            code: self.code(),
+            message: self.message().to_string(),
+            details,
+        }
+    }
+
+    /// Turn extractor failures (e.g. malformed JSON) into `invalid_request` errors
+    pub fn invalid_request(error: impl fmt::Display, _: &HttpRequest) -> actix_web::Error {
+        let details = serde_json::Value::String(error.to_string());
+        Self::from(ErrorCode::InvalidRequest)
+            .with_details(details)
+            .into()
+    }
+}
+
+impl From<ErrorCode> for ApiError {
+    fn from(code: ErrorCode) -> Self {
+        Self::new(code, code.message())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = serde_json::to_value(self.code()).map_err(|_| fmt::Error)?;
+        write!(
+            f,
+            "{}: {}",
+            code.as_str().unwrap_or_default(),
+            self.message()
+        )
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code().status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            // The x402 challenge is returned as is for x402 clients
+            Self::PaymentRequired(challenge) => {
+                response.json(ResultAPI::<(), _>::payment_required(challenge))
+            }
+            Self::RangeNotSatisfiable { size } => response
+                .insert_header((CONTENT_RANGE, format!("bytes */{size}")))
+                .json(ResultAPI::<(), _>::failure(self.body())),
+            Self::RateLimited { retry_after } => response
+                .insert_header((RETRY_AFTER, retry_after.to_string()))
+                .json(ResultAPI::<(), _>::failure(self.body())),
+            Self::Failure { .. } => response.json(ResultAPI::<(), _>::failure(self.body())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_error_response() {
+        let response = ApiError::from(ErrorCode::BucketNotFound).error_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = ApiError::RangeNotSatisfiable { size: 11 }.error_response();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes */11");
+
+        let response = ApiError::RateLimited { retry_after: 7 }.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "7");
+    }
+
+    #[test]
+    fn test_error_body() -> anyhow::Result<()> {
+        let error = ApiError::new(ErrorCode::StorageError, "Failed to list objects")
+            .with_details(serde_json::json!({ "bucket": "media" }));
+        let json = serde_json::to_value(ResultAPI::<(), _>::failure(error.body()))?;
+
+        assert_eq!(json["status"], "Error");
+        assert_eq!(json["data"]["code"], "storage_error");
+        assert_eq!(json["data"]["message"], "Failed to list objects");
+        assert_eq!(json["data"]["details"]["bucket"], "media");
+
+        // Details are omitted when empty
+        let json = serde_json::to_value(ApiError::from(ErrorCode::ClientExists).body())?;
+        assert!(json.get("details").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_catalog() -> anyhow::Result<()> {
+        // Codes are unique and roundtrip through their wire format
+        let mut names = HashSet::new();
+        for code in ErrorCode::ALL {
+            let name = serde_json::to_value(code)?;
+            assert_eq!(serde_json::from_value::<ErrorCode>(name.clone())?, code);
+            assert!(names.insert(name.as_str().unwrap().to_string()));
+        }
+
+        // The documented catalog lists every code with its status
+        let docs = include_str!("error.rs");
+        for code in ErrorCode::ALL {
+            let name = serde_json::to_value(code)?;
+            let row = format!("`{}`", name.as_str().unwrap());
+            let row = docs.lines().find(|l| l.contains(&row)).unwrap();
+            assert!(row.contains(code.status().as_str()), "{row}");
+        }
+        Ok(())
+    }
+}
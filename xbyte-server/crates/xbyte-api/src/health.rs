@@ -1,33 +1,250 @@
-use crate::utils::{API_VERSION, ResultAPI};
+use crate::utils::{API_VERSION, ErrorStatus, ResultAPI};
+use crate::version::API_VERSIONS;
+use crate::x402::X402_VERSION;
+use crate::{BackendCache, Database, MemoryDB, Storage, x402};
 use actix_web::dev::HttpServiceFactory;
-use actix_web::{Responder, get};
+use actix_web::http::StatusCode;
+use actix_web::{Responder, get, web};
+use alloy_provider::Provider;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// The time each dependency has to answer a readiness probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
 
 /// The Health Routes
 #[derive(Debug)]
 pub enum HealthRoute {
     /// The health check status endpoint
     Status,
+    /// The readiness endpoint
+    Ready,
     /// The index endpoint
     Index,
 }
 
 /// The health check status endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses((status = 200, description = "The server is alive", body = String))
+)]
 #[get("/health")]
 async fn health() -> impl Responder {
     ResultAPI::success("OK")
 }
 
+/// The status of a dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentStatus {
+    /// Whether the dependency answered in time
+    pub up: bool,
+    /// How long the probe took
+    pub latency_ms: u64,
+    /// Why the probe failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The readiness of the server and its dependencies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    /// Whether every dependency is up
+    pub ready: bool,
+    /// The status of each dependency
+    pub components: BTreeMap<String, ComponentStatus>,
+}
+
+impl ErrorStatus for Readiness {
+    fn status(&self) -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Run a probe, failing it once the timeout elapses
+async fn probe(check: impl Future<Output = anyhow::Result<()>>) -> ComponentStatus {
+    let start = Instant::now();
+    let result = match actix_web::rt::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("timed out after {PROBE_TIMEOUT:?}")),
+    };
+
+    ComponentStatus {
+        up: result.is_ok(),
+        latency_ms: start.elapsed().as_millis().try_into().unwrap_or(u64::MAX),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+/// The readiness endpoint, probing every dependency in use
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is up", body = Readiness),
+        (status = 503, description = "A dependency is down", body = Readiness),
+    )
+)]
+#[get("/health/ready")]
+async fn ready(
+    provider: web::ThinData<xbyte_evm::Client>,
+    db: web::ThinData<MemoryDB>,
+    cache: web::ThinData<BackendCache>,
+) -> impl Responder {
+    let rpc = probe(async {
+        provider.get_chain_id().await?;
+        Ok(())
+    });
+    let database = probe(async { db.ping() });
+    let facilitator = probe(async {
+        web::block(|| x402::supported(PROBE_TIMEOUT)).await??;
+        Ok(())
+    });
+    // Only AWS S3 storages assume roles through STS, the others never need it
+    let uses_sts = db.get_all_clients().is_ok_and(|clients| {
+        clients
+            .iter()
+            .any(|client| matches!(client.storage, Some(Storage::S3 { .. })))
+    });
+    let sts = async {
+        match uses_sts {
+            true => Some(probe(async { cache.caller_identity().await.map(|_| ()) }).await),
+            false => None,
+        }
+    };
+    let (rpc, database, facilitator, sts) = futures_util::join!(rpc, database, facilitator, sts);
+
+    let mut components = BTreeMap::from([
+        (String::from("rpc"), rpc),
+        (String::from("database"), database),
+        (String::from("facilitator"), facilitator),
+    ]);
+    if let Some(sts) = sts {
+        components.insert(String::from("sts"), sts);
+    }
+    let ready = components.values().all(|c| c.up);
+    for (name, component) in components.iter().filter(|(_, c)| !c.up) {
+        tracing::warn!(?name, ?component.error, "Dependency is not ready");
+    }
+
+    let readiness = Readiness { ready, components };
+    match ready {
+        true => ResultAPI::okay(readiness),
+        false => ResultAPI::failure(readiness),
+    }
+}
+
+/// The versions supported by the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Versions {
+    /// The version of the server
+    pub version: String,
+    /// The versions of the API, e.g. `v1` served under `/v1`
+    pub api: Vec<String>,
+    /// The versions of the x402 protocol accepted for payments
+    pub x402: Vec<u32>,
+}
+
 /// The index endpoint
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "health",
+    responses((status = 200, description = "The supported versions", body = Versions))
+)]
 #[get("/")]
 async fn index() -> impl Responder {
-    ResultAPI::success(API_VERSION)
+    ResultAPI::success(Versions {
+        version: API_VERSION.to_string(),
+        api: API_VERSIONS.map(String::from).to_vec(),
+        x402: vec![X402_VERSION],
+    })
+}
+
+impl HealthRoute {
+    /// Every health route
+    pub const ALL: [Self; 3] = [Self::Status, Self::Ready, Self::Index];
 }
 
 impl HttpServiceFactory for HealthRoute {
     fn register(self, config: &mut actix_web::dev::AppService) {
         match self {
             Self::Status => health.register(config),
+            Self::Ready => ready.register(config),
             Self::Index => index.register(config),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use crate::storage::tests::offline_sts;
+    use actix_web::{App, test};
+    use alloy_primitives::Address;
+
+    #[actix_web::test]
+    async fn test_ready_reports_components() -> anyhow::Result<()> {
+        let provider = xbyte_evm::Client::new("http://127.0.0.1:1")?;
+        let db = MemoryDB::default();
+        let app = App::new()
+            .app_data(web::ThinData(provider))
+            .app_data(web::ThinData(db.clone()))
+            .app_data(web::ThinData(BackendCache::new(offline_sts())))
+            .service(HealthRoute::Ready)
+            .service(HealthRoute::Status);
+        let app = test::init_service(app).await;
+
+        // Unreachable dependencies make the server unready
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: ResultAPI<(), Readiness> = test::read_body_json(res).await;
+        let readiness = body.get_error().unwrap();
+        assert!(!readiness.ready);
+        assert!(readiness.components["database"].up);
+        assert!(!readiness.components["rpc"].up);
+
+        // STS is only probed once a client stores its objects on AWS S3
+        assert!(!readiness.components.contains_key("sts"));
+        let client = Client::new(String::from("test"), Address::ZERO);
+        db.set_client(client.wallet, client.clone())?;
+        let storage = Storage::S3 {
+            role_arn: Default::default(),
+            region: Default::default(),
+        };
+        db.assign_storage(client.wallet, storage)?;
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let body: ResultAPI<(), Readiness> = test::call_and_read_body_json(&app, req).await;
+        let readiness = body.get_error().unwrap();
+        assert!(!readiness.components["sts"].up);
+        assert!(readiness.components["sts"].error.is_some());
+
+        // Liveness stays cheap and independent of the dependencies
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_index_reports_versions() {
+        let app = test::init_service(App::new().service(HealthRoute::Index)).await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let body: ResultAPI<Versions, ()> = test::call_and_read_body_json(&app, req).await;
+        let versions = body.get_data().unwrap();
+        assert_eq!(versions.version, API_VERSION);
+        assert_eq!(versions.api, ["v1"]);
+        assert_eq!(versions.x402, [X402_VERSION]);
+    }
+}
@@ -1,17 +1,45 @@
 mod client;
+mod config;
+mod cors;
 mod db;
+mod error;
 mod health;
+mod metrics;
+mod openapi;
 mod pricing;
+mod propagation;
+mod ratelimit;
 mod s3;
 mod server;
+mod settlement;
+mod storage;
+mod tls;
+mod trace;
 mod utils;
+mod version;
 mod x402;
 
 pub use client::{Client, ClientRoute, Storage};
+pub use config::{
+    AwsConfig, CorsConfig, DatabaseConfig, LogConfig, LogFormat, RateLimitConfig, ServerConfig,
+    TlsConfig, X402Config,
+};
 pub use db::{Database, MemoryDB};
-pub use health::HealthRoute;
-pub use pricing::PricingRoute;
-pub use s3::{S3Route, XByteS3};
+pub use error::{ApiError, ErrorBody, ErrorCode};
+pub use health::{ComponentStatus, HealthRoute, Readiness, Versions};
+pub use metrics::MetricsRoute;
+pub use openapi::OpenApiRoute;
+pub use pricing::{PricingRoute, SetPriceRequest};
+pub use propagation::trace_headers;
+pub use s3::{RegisterRequest, S3Route, XByteS3, register_buckets};
 pub use server::Server;
-pub use utils::ResultAPI;
-pub use x402::{ConfigX402, FacilitatorRequest, FacilitatorResponse};
+pub use storage::{
+    AzureBlobStorage, Backend, BackendCache, ByteStream, GcsStorage, ListQuery, LocalStorage,
+    ObjectInfo, ObjectPage, StorageBackend,
+};
+pub use trace::REQUEST_ID_HEADER;
+pub use utils::{ErrorStatus, ResultAPI};
+pub use version::{API_PREFIX, API_VERSIONS, DEPRECATION_HEADER};
+pub use x402::{
+    ConfigX402, FacilitatorRequest, FacilitatorResponse, Payment, PaymentStatus, X402_VERSION,
+};
@@ -0,0 +1,222 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{HttpServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::ContentType;
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, Responder, get};
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use std::sync::LazyLock;
+
+/// The metrics of the server, exported at `/metrics`
+pub(crate) static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The label of requests that matched no route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// The Prometheus metrics collected by the server
+pub(crate) struct Metrics {
+    /// The registry the metrics are exported from
+    registry: Registry,
+    /// The requests by method, route and status
+    pub requests: IntCounterVec,
+    /// The request latency by method and route
+    pub request_duration: HistogramVec,
+    /// The object bytes delivered by bucket
+    pub bytes_delivered: IntCounterVec,
+    /// The x402 challenges issued by bucket
+    pub challenges: IntCounterVec,
+    /// The payment verifications by outcome
+    pub verifications: IntCounterVec,
+    /// The price charged by bucket, in token units
+    pub price_charged: IntCounterVec,
+    /// The settlements by outcome
+    pub settlements: IntCounterVec,
+    /// The settlement latency
+    pub settlement_duration: Histogram,
+    /// The storage provider latency by provider and operation
+    pub storage_duration: HistogramVec,
+    /// The requests rejected by a rate limit by scope
+    pub rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    /// Create and register the metrics
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some(String::from("xbyte")), None).expect("valid registry prefix");
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("unique counter");
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str]| {
+            let opts = HistogramOpts::new(name, help);
+            let histogram = HistogramVec::new(opts, labels).expect("valid histogram");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("unique histogram");
+            histogram
+        };
+
+        Self {
+            requests: counter(
+                "http_requests_total",
+                "HTTP requests by method, route and status",
+                &["method", "route", "status"],
+            ),
+            request_duration: histogram(
+                "http_request_duration_seconds",
+                "HTTP request latency by method and route",
+                &["method", "route"],
+            ),
+            bytes_delivered: counter(
+                "bytes_delivered_total",
+                "Object bytes delivered by bucket",
+                &["bucket"],
+            ),
+            challenges: counter(
+                "payment_challenges_total",
+                "x402 challenges issued by bucket",
+                &["bucket"],
+            ),
+            verifications: counter(
+                "payment_verifications_total",
+                "x402 payment verifications by outcome",
+                &["outcome"],
+            ),
+            price_charged: counter(
+                "price_charged_total",
+                "Price charged for delivered ranges by bucket, in token units",
+                &["bucket"],
+            ),
+            settlements: counter(
+                "settlements_total",
+                "x402 settlements by outcome",
+                &["outcome"],
+            ),
+            settlement_duration: histogram(
+                "settlement_duration_seconds",
+                "x402 settlement latency",
+                &[],
+            )
+            .with_label_values::<&str>(&[]),
+            storage_duration: histogram(
+                "storage_request_duration_seconds",
+                "Storage provider latency by provider and operation",
+                &["provider", "operation"],
+            ),
+            rate_limited: counter(
+                "rate_limited_total",
+                "Requests rejected by a rate limit by scope",
+                &["scope"],
+            ),
+            registry,
+        }
+    }
+
+    /// Encode the metrics in the Prometheus text format
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let mut buffer = Vec::new();
+        prometheus::TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// The middleware counting requests and their latency by route
+pub(crate) async fn track(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let timer = std::time::Instant::now();
+    let res = next.call(req).await?;
+
+    let route = res.request().match_pattern();
+    let route = route.as_deref().unwrap_or(UNMATCHED_ROUTE);
+    let status = res.status();
+    METRICS
+        .requests
+        .with_label_values(&[method.as_str(), route, status.as_str()])
+        .inc();
+    METRICS
+        .request_duration
+        .with_label_values(&[method.as_str(), route])
+        .observe(timer.elapsed().as_secs_f64());
+
+    Ok(res)
+}
+
+/// The Metrics Routes
+#[derive(Debug)]
+pub enum MetricsRoute {
+    /// The Prometheus export endpoint
+    Export,
+}
+
+/// The Prometheus export endpoint
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "The Prometheus metrics", body = String, content_type = "text/plain"))
+)]
+#[get("/metrics")]
+async fn export() -> impl Responder {
+    match METRICS.encode() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body(body),
+        Err(error) => {
+            tracing::error!(?error, "Failed to encode metrics");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+impl MetricsRoute {
+    /// Every metrics route
+    pub const ALL: [Self; 1] = [Self::Export];
+}
+
+impl HttpServiceFactory for MetricsRoute {
+    fn register(self, config: &mut actix_web::dev::AppService) {
+        match self {
+            Self::Export => export.register(config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, test, web};
+
+    #[actix_web::test]
+    async fn test_export() {
+        let app = App::new()
+            .service(MetricsRoute::Export)
+            .route("/tracked/{id}", web::get().to(HttpResponse::Ok))
+            .wrap(from_fn(track));
+        let app = test::init_service(app).await;
+
+        // Requests are labelled by their route pattern
+        let req = test::TestRequest::get().uri("/tracked/1").to_request();
+        test::call_service(&app, req).await;
+        METRICS
+            .bytes_delivered
+            .with_label_values(&["media"])
+            .inc_by(11);
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(
+            r#"xbyte_http_requests_total{method="GET",route="/tracked/{id}",status="200"}"#
+        ));
+        assert!(body.contains(r#"xbyte_bytes_delivered_total{bucket="media"}"#));
+    }
+}
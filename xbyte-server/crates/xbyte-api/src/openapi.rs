@@ -0,0 +1,288 @@
+use crate::client::api::{self as client, ClientPage, UpdateClientRequest};
+use crate::pricing::SetPriceRequest;
+use crate::s3::api::{self as s3, ObjectDetails, ObjectListing, PricedObject, RegisterRequest};
+use crate::utils::API_VERSION;
+use crate::version::API_PREFIX;
+use crate::x402::{PaymentRequest, X402Response};
+use crate::{Client, ComponentStatus, ErrorBody, ErrorCode, ObjectInfo, Readiness, Storage};
+use crate::{Versions, health, metrics, pricing};
+use actix_web::dev::HttpServiceFactory;
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, Responder, get};
+use std::sync::LazyLock;
+use utoipa::openapi::schema::{Object, ObjectBuilder, Type};
+use utoipa::openapi::{OpenApi as Document, RefOr, Schema, Server};
+use utoipa::{Modify, OpenApi};
+use utoipa_scalar::Scalar;
+
+/// The OpenAPI document of the API, built once
+static DOCUMENT: LazyLock<Document> = LazyLock::new(|| {
+    let mut document = ApiDoc::openapi();
+    document.info.version = API_VERSION.to_string();
+    document.servers = Some(vec![Server::new(API_PREFIX)]);
+    document
+});
+
+/// The OpenAPI document, generated from the handlers and their schemas
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "xByte API", description = "The pay-per-byte content monetization protocol"),
+    paths(
+        health::health,
+        health::ready,
+        health::index,
+        metrics::export,
+        pricing::set_price,
+        pricing::get_price,
+        client::create_client,
+        client::get_client,
+        client::update_client,
+        client::delete_client,
+        client::list_clients,
+        s3::get_all_buckets,
+        s3::get_all_objects,
+        s3::get_object,
+        s3::get_object_info,
+        s3::head_object,
+        s3::register_bucket,
+        s3::unregister_bucket,
+    ),
+    components(schemas(
+        Client,
+        Storage<String>,
+        UpdateClientRequest,
+        ClientPage,
+        SetPriceRequest,
+        RegisterRequest,
+        ObjectInfo,
+        PricedObject,
+        ObjectListing,
+        ObjectDetails,
+        ComponentStatus,
+        Readiness,
+        Versions,
+        ErrorCode,
+        ErrorBody,
+        PaymentRequest<String, String>,
+        X402Response<String, String>,
+    )),
+    modifiers(&Envelope),
+    tags(
+        (name = "health", description = "Liveness, readiness and metrics"),
+        (name = "pricing", description = "Object prices"),
+        (name = "client", description = "Content platforms"),
+        (name = "s3", description = "Buckets and paid objects"),
+    )
+)]
+struct ApiDoc;
+
+/// Wraps the JSON bodies in the `{ status, data }` envelope of `ResultAPI`
+///
+/// Successes carry `status: Success`, failures `status: Error`, and the x402 challenge of a
+/// `402` is returned as is for x402 clients.
+struct Envelope;
+
+impl Envelope {
+    /// The envelope of a body with the given status
+    fn wrap(status: &str, data: RefOr<Schema>) -> Object {
+        ObjectBuilder::new()
+            .property(
+                "status",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .enum_values(Some([status])),
+            )
+            .property("data", data)
+            .required("status")
+            .required("data")
+            .build()
+    }
+}
+
+impl Modify for Envelope {
+    fn modify(&self, openapi: &mut Document) {
+        let operations = openapi
+            .paths
+            .paths
+            .values_mut()
+            .flat_map(|item| {
+                [
+                    &mut item.get,
+                    &mut item.post,
+                    &mut item.patch,
+                    &mut item.delete,
+                    &mut item.head,
+                ]
+            })
+            .flatten();
+
+        for operation in operations {
+            for (code, response) in operation.responses.responses.iter_mut() {
+                let status = match code.as_str() {
+                    "402" => continue,
+                    code if code.starts_with('2') => "Success",
+                    _ => "Error",
+                };
+                let RefOr::T(response) = response else {
+                    continue;
+                };
+                let Some(RefOr::T(content)) = response.content.get_mut("application/json") else {
+                    continue;
+                };
+                if let Some(schema) = content.schema.take() {
+                    content.schema = Some(Self::wrap(status, schema).into());
+                }
+            }
+        }
+    }
+}
+
+/// The OpenAPI Routes
+#[derive(Debug)]
+pub enum OpenApiRoute {
+    /// The OpenAPI document endpoint
+    Spec,
+    /// The API reference endpoint
+    Docs,
+}
+
+impl OpenApiRoute {
+    /// Every OpenAPI route
+    pub const ALL: [Self; 2] = [Self::Spec, Self::Docs];
+}
+
+/// The OpenAPI document endpoint
+#[get("/openapi.json")]
+async fn spec() -> impl Responder {
+    HttpResponse::Ok().json(&*DOCUMENT)
+}
+
+/// The API reference endpoint, rendering the document
+#[get("/docs")]
+async fn docs() -> impl Responder {
+    static HTML: LazyLock<String> =
+        LazyLock::new(|| Scalar::new(DOCUMENT.clone()).title("xByte API").to_html());
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(HTML.as_str())
+}
+
+impl HttpServiceFactory for OpenApiRoute {
+    fn register(self, config: &mut actix_web::dev::AppService) {
+        match self {
+            Self::Spec => spec.register(config),
+            Self::Docs => docs.register(config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientRoute, HealthRoute, MetricsRoute, PricingRoute, S3Route};
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{App, test, web};
+
+    /// The method and path of every documented operation, with parameters filled in
+    fn operations() -> Vec<(Method, String)> {
+        let mut operations = Vec::new();
+        for (path, item) in &DOCUMENT.paths.paths {
+            let methods = [
+                (Method::GET, &item.get),
+                (Method::POST, &item.post),
+                (Method::PATCH, &item.patch),
+                (Method::DELETE, &item.delete),
+                (Method::HEAD, &item.head),
+            ];
+            let path = path.replace(['{', '}'], "");
+            for (method, _) in methods.into_iter().filter(|(_, o)| o.is_some()) {
+                operations.push((method, path.clone()));
+            }
+        }
+        operations
+    }
+
+    /// The documented operations served by a single route
+    async fn served_by(route: impl HttpServiceFactory + 'static) -> Vec<(Method, String)> {
+        let app = App::new()
+            .service(route)
+            .default_service(web::to(|| async { HttpResponse::ImATeapot().finish() }));
+        let app = test::init_service(app).await;
+
+        let mut served = Vec::new();
+        for (method, path) in operations() {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri(&path)
+                .to_request();
+            let status = test::call_service(&app, req).await.status();
+            if ![StatusCode::IM_A_TEAPOT, StatusCode::METHOD_NOT_ALLOWED].contains(&status) {
+                served.push((method, path));
+            }
+        }
+        served
+    }
+
+    #[actix_web::test]
+    async fn test_routes_match_spec() {
+        let mut routes = Vec::new();
+        for route in HealthRoute::ALL {
+            routes.push((format!("{route:?}"), served_by(route).await));
+        }
+        for route in MetricsRoute::ALL {
+            routes.push((format!("{route:?}"), served_by(route).await));
+        }
+        for route in PricingRoute::ALL {
+            routes.push((format!("{route:?}"), served_by(route).await));
+        }
+        for route in ClientRoute::ALL {
+            routes.push((format!("{route:?}"), served_by(route).await));
+        }
+        for route in S3Route::ALL {
+            routes.push((format!("{route:?}"), served_by(route).await));
+        }
+
+        // Every route is documented once, and every documented operation is served
+        for (route, served) in &routes {
+            assert_eq!(served.len(), 1, "{route} serves {served:?}");
+        }
+        let mut served = routes.into_iter().flat_map(|(_, s)| s).collect::<Vec<_>>();
+        let mut documented = operations();
+        served.sort_by_key(|(m, p)| (p.clone(), m.to_string()));
+        documented.sort_by_key(|(m, p)| (p.clone(), m.to_string()));
+        assert_eq!(served, documented);
+    }
+
+    #[actix_web::test]
+    async fn test_serve_spec() -> anyhow::Result<()> {
+        let app = App::new()
+            .service(OpenApiRoute::Spec)
+            .service(OpenApiRoute::Docs);
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let document: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+        assert_eq!(document["info"]["version"], API_VERSION);
+        assert_eq!(document["servers"][0]["url"], API_PREFIX);
+
+        // Bodies are enveloped, the x402 challenge is not
+        let get_object = &document["paths"]["/s3/bucket/{bucket}/object/{object}"]["get"];
+        let json = |code: &str| &get_object["responses"][code]["content"]["application/json"];
+        assert_eq!(
+            json("402")["schema"]["$ref"],
+            "#/components/schemas/X402Response_String_String"
+        );
+        assert_eq!(
+            json("404")["schema"]["properties"]["data"]["$ref"],
+            "#/components/schemas/ErrorBody"
+        );
+        assert!(document["components"]["schemas"]["RegisterRequest"].is_object());
+
+        let req = test::TestRequest::get().uri("/docs").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        Ok(())
+    }
+}
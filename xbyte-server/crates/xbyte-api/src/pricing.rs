@@ -1,8 +1,9 @@
-use crate::{Database, MemoryDB, ResultAPI};
+use crate::{ApiError, Database, ErrorBody, ErrorCode, MemoryDB, ResultAPI};
 use actix_web::Responder;
 use actix_web::dev::HttpServiceFactory;
 use actix_web::{get, post, web};
 use serde::Deserialize;
+use utoipa::ToSchema;
 
 /// The Pricing Routes
 #[derive(Debug)]
@@ -13,6 +14,11 @@ pub enum PricingRoute {
     GetPrice,
 }
 
+impl PricingRoute {
+    /// Every pricing route
+    pub const ALL: [Self; 2] = [Self::SetPrice, Self::GetPrice];
+}
+
 impl HttpServiceFactory for PricingRoute {
     fn register(self, config: &mut actix_web::dev::AppService) {
         match self {
@@ -23,7 +29,7 @@ impl HttpServiceFactory for PricingRoute {
 }
 
 /// The request to set a price
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SetPriceRequest {
     /// The bucket
@@ -34,33 +40,57 @@ pub struct SetPriceRequest {
     pub price: u64,
 }
 
+#[utoipa::path(
+    post,
+    path = "/price",
+    tag = "pricing",
+    request_body = SetPriceRequest,
+    responses(
+        (status = 200, description = "The price is set, with no data", body = ()),
+        (status = 400, description = "Malformed request", body = ErrorBody),
+        (status = 500, description = "The price was not set", body = ErrorBody),
+    )
+)]
 #[post("/price")]
 async fn set_price(
     payload: web::Json<SetPriceRequest>,
     db: web::ThinData<MemoryDB>,
-) -> impl Responder {
+) -> Result<impl Responder, ApiError> {
     let payload = payload.into_inner();
 
     match db.set_price((payload.bucket, payload.object), payload.price) {
-        Ok(key) => ResultAPI::okay(key),
+        Ok(key) => Ok(ResultAPI::success(key)),
         Err(error) => {
             tracing::error!(?error, "Failed to set price");
-            ResultAPI::failure("Price not set")
+            Err(ApiError::new(ErrorCode::InternalError, "Price not set"))
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/price/{bucket}/{object}",
+    tag = "pricing",
+    params(
+        ("bucket" = String, Path, description = "The bucket"),
+        ("object" = String, Path, description = "The object key"),
+    ),
+    responses(
+        (status = 200, description = "The price in USDC / 1MB", body = u64),
+        (status = 404, description = "No price is set", body = ErrorBody),
+    )
+)]
 #[get("/price/{bucket}/{object}")]
 async fn get_price(
     key: web::Path<(String, String)>,
     db: web::ThinData<MemoryDB>,
-) -> impl Responder {
+) -> Result<impl Responder, ApiError> {
     // Get the price
     match db.get_price(&key) {
-        Ok(price) => ResultAPI::okay(price),
+        Ok(price) => Ok(ResultAPI::success(price)),
         Err(error) => {
             tracing::error!(?error, "Failed to get price");
-            ResultAPI::failure("Price not found")
+            Err(ApiError::from(ErrorCode::PriceNotFound))
         }
     }
 }
@@ -0,0 +1,102 @@
+use actix_web::http::header::HeaderMap;
+use aws_sdk_s3::config::interceptors::BeforeTransmitInterceptorContextMut;
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use tracing::Span;
+
+/// The W3C trace context headers of the current span, sent along outgoing requests
+///
+/// Empty unless the `otel` feature is enabled and a propagator is installed.
+pub fn trace_headers() -> Vec<(String, String)> {
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = Span::current().context();
+        let mut headers = std::collections::HashMap::new();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut headers)
+        });
+        headers.into_iter().collect()
+    }
+
+    #[cfg(not(feature = "otel"))]
+    Vec::new()
+}
+
+/// Continue the trace of the caller when the request carries a `traceparent` header
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn continue_trace(span: &Span, headers: &HeaderMap) {
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let carrier: std::collections::HashMap<String, String> = headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let context = opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&carrier)
+        });
+        let _ = span.set_parent(context);
+    }
+}
+
+/// The AWS SDK interceptor adding the trace context to S3 and STS requests
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TraceInterceptor;
+
+impl Intercept for TraceInterceptor {
+    fn name(&self) -> &'static str {
+        "TraceInterceptor"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _: &RuntimeComponents,
+        _: &mut ConfigBag,
+    ) -> Result<(), aws_sdk_s3::error::BoxError> {
+        let headers = context.request_mut().headers_mut();
+        for (name, value) in trace_headers() {
+            headers.insert(name, value);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_trace_propagation() {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                HeaderName::from_static("traceparent"),
+                HeaderValue::from_str(&format!("00-{TRACE_ID}-00f067aa0ba902b7-01")).unwrap(),
+            );
+
+            // The incoming trace is continued by the outgoing requests
+            let span = tracing::info_span!("request");
+            continue_trace(&span, &headers);
+            let outgoing = span.in_scope(trace_headers);
+            let (_, traceparent) = outgoing.iter().find(|(k, _)| k == "traceparent").unwrap();
+            assert!(traceparent.starts_with(&format!("00-{TRACE_ID}-")));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+}
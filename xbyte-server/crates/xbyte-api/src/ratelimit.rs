@@ -0,0 +1,282 @@
+use crate::ApiError;
+use crate::config::RateLimitConfig;
+use crate::metrics::METRICS;
+use actix_web::HttpRequest;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The keys tracked before full buckets are forgotten
+const MAX_KEYS: usize = 100_000;
+/// The least time between two sweeps of the full buckets, so a full limiter sweeps rarely
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+/// The key of requests without a known peer address
+const UNKNOWN_IP: &str = "unknown";
+
+/// The tokens left to a key and when they were last refilled
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The buckets of a limiter and when the full ones were last forgotten
+#[derive(Debug, Default)]
+struct Buckets {
+    keys: HashMap<String, Bucket>,
+    swept: Option<Instant>,
+}
+
+/// A token bucket per key, holding up to the limit and refilled over a minute
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimiter {
+    /// The requests allowed per minute, 0 disables the limiter
+    limit: u32,
+    /// The bucket of each key
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `limit` requests per minute and key
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            buckets: Default::default(),
+        }
+    }
+
+    /// The tokens refilled per second
+    fn rate(&self) -> f64 {
+        f64::from(self.limit) / 60.0
+    }
+
+    /// The tokens of a bucket at `now`
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate()).min(f64::from(self.limit))
+    }
+
+    /// The time until a bucket holding `tokens` gets a full token back
+    fn retry_after(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1.0 - tokens) / self.rate())
+    }
+
+    /// Take a token of the key at `now`, returning how long to wait when none is left
+    ///
+    /// Once `MAX_KEYS` are tracked, the full buckets are swept at most every `SWEEP_INTERVAL`
+    /// and new keys are let through untracked until a sweep makes room for them.
+    fn acquire_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let Buckets { keys, swept } = &mut *buckets;
+        if keys.len() >= MAX_KEYS && !keys.contains_key(key) {
+            if swept.is_none_or(|swept| now.saturating_duration_since(swept) >= SWEEP_INTERVAL) {
+                // A full bucket behaves like a missing one
+                let limit = f64::from(self.limit);
+                keys.retain(|_, bucket| self.refill(bucket, now) < limit);
+                *swept = Some(now);
+            }
+            if keys.len() >= MAX_KEYS {
+                return Ok(());
+            }
+        }
+
+        let bucket = keys.entry(key.to_string()).or_insert(Bucket {
+            tokens: f64::from(self.limit),
+            updated: now,
+        });
+        let tokens = self.refill(bucket, now);
+        *bucket = Bucket {
+            tokens: (tokens - 1.0).max(0.0),
+            updated: now,
+        };
+
+        match tokens >= 1.0 {
+            true => Ok(()),
+            false => Err(self.retry_after(tokens)),
+        }
+    }
+
+    /// The time until the key gets a token at `now`, without taking it
+    fn wait_at(&self, key: &str, now: Instant) -> Option<Duration> {
+        if self.limit == 0 {
+            return None;
+        }
+
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let tokens = self.refill(buckets.keys.get(key)?, now);
+        (tokens < 1.0).then(|| self.retry_after(tokens))
+    }
+
+    /// Take a token of the key, returning how long to wait when none is left
+    pub fn acquire(&self, key: &str) -> Result<(), Duration> {
+        self.acquire_at(key, Instant::now())
+    }
+
+    /// The time until the key gets a token, without taking it
+    pub fn wait(&self, key: &str) -> Option<Duration> {
+        self.wait_at(key, Instant::now())
+    }
+}
+
+/// The rate limits of the object routes
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimits {
+    /// The requests of each client IP
+    ip: RateLimiter,
+    /// The paid requests of each payer
+    payer: RateLimiter,
+    /// The invalid payments of each client IP
+    invalid_payment: RateLimiter,
+    /// Whether the client IP is read from the forwarding headers
+    trust_proxy: bool,
+}
+
+impl RateLimits {
+    /// Create the limits of the configuration
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            ip: RateLimiter::new(config.per_ip),
+            payer: RateLimiter::new(config.per_payer),
+            invalid_payment: RateLimiter::new(config.invalid_payment),
+            trust_proxy: config.trust_proxy,
+        }
+    }
+
+    /// The IP of the client, only trusting `Forwarded` and `X-Forwarded-For` behind a proxy
+    pub fn client_ip(&self, req: &HttpRequest) -> String {
+        let info = req.connection_info();
+        let ip = match self.trust_proxy {
+            true => info.realip_remote_addr().map(String::from),
+            false => info.peer_addr().map(String::from),
+        };
+        ip.unwrap_or_else(|| UNKNOWN_IP.to_string())
+    }
+
+    /// Turn an exhausted limit into a `rate_limited` error
+    fn limited(scope: &str, key: &str, retry_after: Duration) -> ApiError {
+        tracing::warn!(scope, key, ?retry_after, "Rate limit exceeded");
+        METRICS.rate_limited.with_label_values(&[scope]).inc();
+        ApiError::RateLimited {
+            retry_after: (retry_after.as_secs_f64().ceil() as u64).max(1),
+        }
+    }
+
+    /// Count a request of the client IP
+    pub fn check_ip(&self, ip: &str) -> Result<(), ApiError> {
+        self.ip
+            .acquire(ip)
+            .map_err(|wait| Self::limited("ip", ip, wait))
+    }
+
+    /// Reject clients that used up their invalid payments, before any lookup
+    pub fn check_invalid_payments(&self, ip: &str) -> Result<(), ApiError> {
+        match self.invalid_payment.wait(ip) {
+            Some(wait) => Err(Self::limited("invalid_payment", ip, wait)),
+            None => Ok(()),
+        }
+    }
+
+    /// Count an invalid payment of the client IP
+    pub fn invalid_payment(&self, ip: &str) -> Result<(), ApiError> {
+        self.invalid_payment
+            .acquire(ip)
+            .map_err(|wait| Self::limited("invalid_payment", ip, wait))
+    }
+
+    /// Count a paid request of the payer
+    pub fn check_payer(&self, payer: &str) -> Result<(), ApiError> {
+        let payer = payer.to_lowercase();
+        self.payer
+            .acquire(&payer)
+            .map_err(|wait| Self::limited("payer", &payer, wait))
+    }
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self::new(&RateLimitConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(60);
+        let now = Instant::now();
+
+        // The whole budget can be spent at once
+        for _ in 0..60 {
+            assert!(limiter.acquire_at("1.2.3.4", now).is_ok());
+        }
+        let wait = limiter.acquire_at("1.2.3.4", now).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+        assert_eq!(
+            limiter.wait_at("1.2.3.4", now),
+            Some(Duration::from_secs(1))
+        );
+
+        // Keys are limited independently and tokens come back over time
+        assert!(limiter.acquire_at("5.6.7.8", now).is_ok());
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.wait_at("1.2.3.4", later), None);
+        assert!(limiter.acquire_at("1.2.3.4", later).is_ok());
+
+        // A limit of zero disables the limiter
+        let disabled = RateLimiter::new(0);
+        for _ in 0..100 {
+            assert!(disabled.acquire_at("1.2.3.4", now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_token_bucket_at_capacity() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        for i in 0..MAX_KEYS {
+            assert!(limiter.acquire_at(&i.to_string(), now).is_ok());
+        }
+        let tracked = || limiter.buckets.lock().unwrap().keys.len();
+
+        // Nothing is full yet, so new keys go untracked rather than sweeping on every request
+        assert!(limiter.acquire_at("new", now).is_ok());
+        assert!(limiter.acquire_at("new", now).is_ok());
+        assert_eq!(tracked(), MAX_KEYS);
+        let swept = limiter.buckets.lock().unwrap().swept;
+        assert_eq!(swept, Some(now));
+
+        // Tracked keys stay limited
+        assert!(limiter.acquire_at("0", now).is_err());
+
+        // Once the buckets are full again, the next sweep forgets them
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.acquire_at("new", later).is_ok());
+        assert_eq!(tracked(), 1);
+        assert!(limiter.acquire_at("new", later).is_err());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "203.0.113.7"))
+            .to_http_request();
+
+        // Forwarding headers are spoofable unless a proxy sets them
+        let limits = RateLimits::default();
+        assert_eq!(limits.client_ip(&req), "10.0.0.1");
+
+        let config = RateLimitConfig {
+            trust_proxy: true,
+            ..Default::default()
+        };
+        assert_eq!(RateLimits::new(&config).client_ip(&req), "203.0.113.7");
+    }
+}
@@ -1,7 +1,31 @@
-use crate::{ConfigX402, Database, MemoryDB, ResultAPI, Storage, XByteS3, utils, x402};
+use crate::metrics::METRICS;
+use crate::ratelimit::RateLimits;
+use crate::settlement::Settlements;
+use crate::x402::X402Response;
+use crate::{
+    ApiError, BackendCache, Client, ConfigX402, Database, ErrorBody, ErrorCode, MemoryDB,
+    ResultAPI, Storage,
+};
+use crate::{Backend, StorageBackend};
+use crate::{ListQuery, ObjectInfo, Payment, PaymentStatus};
+use crate::{storage, utils, x402};
 use actix_web::dev::HttpServiceFactory;
-use actix_web::{HttpRequest, Responder, get, post, web};
+use actix_web::{HttpRequest, Responder, delete, get, post, route, web};
 use serde::{Deserialize, Serialize};
+use tracing::Span;
+use utoipa::{IntoParams, ToSchema};
+
+/// The price in USDC / 1MB of objects without an explicit price
+const DEFAULT_PRICE: u64 = 1000;
+
+/// The header carrying the object size on `HEAD` requests
+pub(crate) const OBJECT_SIZE_HEADER: &str = "X-Object-Size";
+/// The header carrying the object MIME type on `HEAD` requests
+pub(crate) const OBJECT_TYPE_HEADER: &str = "X-Object-Type";
+/// The header carrying the price per MB on `HEAD` requests
+pub(crate) const PRICE_HEADER: &str = "X-Price-Per-MB";
+/// The header carrying the full-object price on `HEAD` requests
+pub(crate) const TOTAL_PRICE_HEADER: &str = "X-Price-Total";
 
 /// The S3 Routes
 #[derive(Debug)]
@@ -12,8 +36,27 @@ pub enum S3Route {
     GetAllObjects,
     /// The get object endpoint
     GetObject,
+    /// The get object info endpoint
+    GetObjectInfo,
+    /// The head object endpoint
+    HeadObject,
     /// The register bucket endpoint
     RegisterBucket,
+    /// The unregister bucket endpoint
+    UnregisterBucket,
+}
+
+impl S3Route {
+    /// Every S3 route
+    pub const ALL: [Self; 7] = [
+        Self::GetAllBuckets,
+        Self::GetAllObjects,
+        Self::GetObject,
+        Self::GetObjectInfo,
+        Self::HeadObject,
+        Self::RegisterBucket,
+        Self::UnregisterBucket,
+    ];
 }
 
 impl HttpServiceFactory for S3Route {
@@ -22,112 +65,297 @@ impl HttpServiceFactory for S3Route {
             Self::GetAllBuckets => get_all_buckets.register(config),
             Self::GetAllObjects => get_all_objects.register(config),
             Self::GetObject => get_object.register(config),
+            Self::GetObjectInfo => get_object_info.register(config),
+            Self::HeadObject => head_object.register(config),
             Self::RegisterBucket => register_bucket.register(config),
+            Self::UnregisterBucket => unregister_bucket.register(config),
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/s3/bucket",
+    tag = "s3",
+    responses(
+        (status = 200, description = "The registered buckets", body = Vec<String>),
+        (status = 502, description = "A storage provider failed", body = ErrorBody),
+    )
+)]
 #[get("/s3/bucket")]
 async fn get_all_buckets(
-    sts: web::ThinData<aws_sdk_sts::Client>,
+    cache: web::ThinData<BackendCache>,
     db: web::ThinData<MemoryDB>,
-) -> impl Responder {
+) -> Result<impl Responder, ApiError> {
     let mut buckets = Vec::new();
 
-    let storages = match db.get_all_storages() {
-        Ok(s) => s,
+    let clients = match db.get_all_clients() {
+        Ok(c) => c,
         Err(error) => {
             tracing::error!(?error, "Failed to get all clients");
-            return ResultAPI::failure("Failed to get all clients");
+            return Err(ApiError::new(
+                ErrorCode::InternalError,
+                "Failed to get all clients",
+            ));
         }
     };
 
-    for storage in storages {
-        let s3 = match XByteS3::new_assumed_role(
-            &sts,
-            storage.role_arn(),
-            "xbyte-s3",
-            storage.region().clone(),
-        )
-        .await
-        {
-            Ok(s3) => s3,
+    for client in clients {
+        let (Some(id), Some(storage)) = (client.id, client.storage) else {
+            continue;
+        };
+        let backend = match cache.backend(&storage).await {
+            Ok(b) => b,
             Err(error) => {
-                tracing::error!(?error, "Failed to create S3 client");
+                tracing::error!(?error, "Failed to create storage client");
                 continue;
             }
         };
 
-        let objects = match s3.list_buckets().await {
+        let objects = match backend.list_containers().await {
             Ok(o) => o,
             Err(error) => {
                 tracing::error!(?error, "Error listing buckets");
-                return ResultAPI::failure("Failed to list buckets");
+                return Err(ApiError::new(
+                    ErrorCode::StorageError,
+                    "Failed to list buckets",
+                ));
             }
         };
 
-        buckets.extend(objects.into_iter().filter_map(|b| b.name));
+        // Only expose buckets the client registered, storages may share an account
+        let registered = objects
+            .into_iter()
+            .filter(|name| db.get_bucket(name).is_ok_and(|owner| owner == id));
+        buckets.extend(registered);
     }
 
-    ResultAPI::okay(buckets)
+    Ok(ResultAPI::success(buckets))
 }
 
+/// An object along with its resolved price
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PricedObject {
+    /// The object metadata
+    #[serde(flatten)]
+    pub info: ObjectInfo,
+    /// The price in USDC / 1MB
+    pub price: u64,
+}
+
+/// A page of objects in a bucket
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectListing {
+    /// The objects of the page
+    pub objects: Vec<PricedObject>,
+    /// The common prefixes grouped by the delimiter
+    pub prefixes: Vec<String>,
+    /// The cursor of the next page, if there is one
+    pub next_cursor: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/s3/bucket/{bucket}/objects",
+    tag = "s3",
+    params(("bucket" = String, Path, description = "The bucket"), ListQuery),
+    responses(
+        (status = 200, description = "A page of priced objects", body = ObjectListing),
+        (status = 404, description = "The bucket is not registered", body = ErrorBody),
+        (status = 502, description = "The storage provider failed", body = ErrorBody),
+    )
+)]
 #[get("/s3/bucket/{bucket}/objects")]
 async fn get_all_objects(
-    sts: web::ThinData<aws_sdk_sts::Client>,
+    cache: web::ThinData<BackendCache>,
     bucket: web::Path<String>,
+    query: web::Query<ListQuery>,
     db: web::ThinData<MemoryDB>,
-) -> impl Responder {
-    // Get the bucket owner
-    let client = match db
-        .get_bucket(&bucket)
-        .and_then(|a| db.get_client(&a))
-        .map(|c| c.storage)
-    {
-        Ok(Some(storage)) => storage,
-        Ok(_) => {
-            tracing::error!("Bucket owner not found");
-            panic!("Bucket owner not found");
-        }
+) -> Result<impl Responder, ApiError> {
+    let (_, storage) = bucket_owner(&db, &bucket)?;
+    let backend = storage_backend(&cache, &storage).await?;
+
+    let page = match backend.list_objects(&bucket, &query).await {
+        Ok(page) => page,
         Err(error) => {
-            tracing::error!(?error, "Failed to get bucket owner");
-            return ResultAPI::failure("Failed to get bucket owner");
+            tracing::error!(?error, "Error listing objects");
+            return Err(ApiError::new(
+                ErrorCode::StorageError,
+                "Failed to list objects",
+            ));
         }
     };
 
-    let s3 = match XByteS3::new_assumed_role(
-        &sts,
-        client.role_arn(),
-        "xbyte-s3",
-        client.region().clone(),
-    )
-    .await
+    // Resolve the price of every object
+    let objects = page
+        .objects
+        .into_iter()
+        .map(|info| {
+            let key = (bucket.to_string(), info.key.clone());
+            let price = db.get_price(&key).unwrap_or(DEFAULT_PRICE);
+            PricedObject { info, price }
+        })
+        .collect();
+
+    Ok(ResultAPI::success(ObjectListing {
+        objects,
+        prefixes: page.prefixes,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+/// The metadata of an object along with its prices
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectDetails {
+    /// The object metadata
+    #[serde(flatten)]
+    pub info: ObjectInfo,
+    /// The price in USDC / 1MB
+    pub price: u64,
+    /// The price of the full object
+    pub total_price: u64,
+}
+
+/// Get the owner of a bucket along with its storage
+fn bucket_owner(db: &MemoryDB, bucket: &str) -> Result<(Client, Storage<String>), ApiError> {
+    Span::current().record("bucket", bucket);
+    let client = match db
+        .get_bucket(&bucket.to_string())
+        .and_then(|c| db.get_client(&c))
     {
-        Ok(s3) => s3,
+        Ok(client) => client,
         Err(error) => {
-            tracing::error!(?error, "Failed to create S3 client");
-            return ResultAPI::failure("Failed to create S3 client");
+            tracing::warn!(?error, ?bucket, "Failed to get bucket owner");
+            return Err(ApiError::from(ErrorCode::BucketNotFound));
         }
     };
 
-    let objects = match s3.list_objects(&bucket).await {
-        Ok(objects) => objects,
+    match client.storage.clone() {
+        Some(storage) => Ok((client, storage)),
+        None => {
+            tracing::error!(?bucket, "Bucket owner has no storage");
+            Err(ApiError::from(ErrorCode::BucketOwnerNotFound))
+        }
+    }
+}
+
+/// Resolve a storage backend
+async fn storage_backend(
+    cache: &BackendCache,
+    storage: &Storage<String>,
+) -> Result<Backend, ApiError> {
+    cache.backend(storage).await.map_err(|error| {
+        tracing::error!(?error, "Failed to create storage client");
+        ApiError::new(ErrorCode::StorageError, "Failed to create storage client")
+    })
+}
+
+/// Get the metadata of an object from its owner's storage and resolve its prices
+async fn object_details(
+    cache: &BackendCache,
+    db: &MemoryDB,
+    path: (String, String),
+) -> Result<ObjectDetails, ApiError> {
+    Span::current().record("object", &path.1);
+    let (_, storage) = bucket_owner(db, &path.0)?;
+    let backend = storage_backend(cache, &storage).await?;
+    let info = match backend.head_object(&path.0, &path.1).await {
+        Ok(info) => info,
         Err(error) => {
-            tracing::error!(?error, "Error listing objects");
-            return ResultAPI::failure("Failed to list objects");
+            tracing::warn!(?error, ?path, "Failed to get object info");
+            return Err(ApiError::from(ErrorCode::ObjectNotFound));
         }
     };
 
-    let names = objects
-        .into_iter()
-        .filter_map(|o| o.key)
-        .collect::<Vec<_>>();
+    let price = db.get_price(&path).unwrap_or(DEFAULT_PRICE);
+    let total_price = utils::calculate_price(price as f32, info.size as f32);
+
+    Ok(ObjectDetails {
+        info,
+        price,
+        total_price,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/s3/bucket/{bucket}/object/{object}/info",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "The bucket"),
+        ("object" = String, Path, description = "The object key"),
+    ),
+    responses(
+        (status = 200, description = "The object metadata and prices", body = ObjectDetails),
+        (status = 404, description = "The bucket or object does not exist", body = ErrorBody),
+    )
+)]
+#[get("/s3/bucket/{bucket}/object/{object}/info")]
+async fn get_object_info(
+    cache: web::ThinData<BackendCache>,
+    path: web::Path<(String, String)>,
+    db: web::ThinData<MemoryDB>,
+) -> Result<impl Responder, ApiError> {
+    let details = object_details(&cache, &db, path.into_inner()).await?;
+    Ok(ResultAPI::success(details))
+}
+
+#[utoipa::path(
+    head,
+    path = "/s3/bucket/{bucket}/object/{object}",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "The bucket"),
+        ("object" = String, Path, description = "The object key"),
+    ),
+    responses(
+        (status = 200, description = "The object metadata and prices as headers", headers(
+            ("X-Object-Size" = u64, description = "The size in bytes"),
+            ("X-Object-Type" = String, description = "The MIME type"),
+            ("X-Price-Per-MB" = u64, description = "The price in USDC / 1MB"),
+            ("X-Price-Total" = u64, description = "The price of the full object"),
+        )),
+        (status = 404, description = "The bucket or object does not exist"),
+    )
+)]
+#[route("/s3/bucket/{bucket}/object/{object}", method = "HEAD")]
+async fn head_object(
+    cache: web::ThinData<BackendCache>,
+    path: web::Path<(String, String)>,
+    db: web::ThinData<MemoryDB>,
+) -> Result<impl Responder, ApiError> {
+    let details = object_details(&cache, &db, path.into_inner()).await?;
+
+    // The body is dropped on `HEAD`, so the metadata is exposed as headers
+    let mut headers = vec![
+        (OBJECT_SIZE_HEADER, details.info.size.to_string()),
+        (PRICE_HEADER, details.price.to_string()),
+        (TOTAL_PRICE_HEADER, details.total_price.to_string()),
+    ];
+    headers.extend(details.info.etag.clone().map(|e| ("ETag", e)));
+    headers.extend(
+        details
+            .info
+            .content_type
+            .clone()
+            .map(|t| (OBJECT_TYPE_HEADER, t)),
+    );
 
-    ResultAPI::okay(names)
+    let response = headers.into_iter().fold(
+        ResultAPI::success(details).customize(),
+        |response, header| response.insert_header(header),
+    );
+
+    Ok(response)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The range of an object to pay for and read
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
 #[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
 pub struct RangeRequest {
     /// The offset to start the range from
     pub offset: u64,
@@ -135,142 +363,732 @@ pub struct RangeRequest {
     pub length: u64,
 }
 
+#[utoipa::path(
+    get,
+    path = "/s3/bucket/{bucket}/object/{object}",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "The bucket"),
+        ("object" = String, Path, description = "The object key"),
+        RangeRequest,
+        ("X-Payment" = Option<String>, Header, description = "The base64 x402 payment payload"),
+    ),
+    responses(
+        (status = 200, description = "The bytes of the range, the payment settles in the background", body = Vec<u8>),
+        (status = 401, description = "The X-Payment header cannot be decoded", body = ErrorBody),
+        (status = 402, description = "Pay the x402 challenge and retry", body = X402Response<String, String>),
+        (status = 404, description = "The bucket or object does not exist", body = ErrorBody),
+        (status = 416, description = "The range lies outside of the object", body = ErrorBody),
+        (status = 429, description = "Too many requests", body = ErrorBody, headers(
+            ("Retry-After" = u64, description = "The seconds to wait"),
+        )),
+        (status = 502, description = "The storage provider or payment facilitator failed", body = ErrorBody),
+    )
+)]
 #[get("/s3/bucket/{bucket}/object/{object}")]
+#[allow(clippy::too_many_arguments)]
 async fn get_object(
-    sts: web::ThinData<aws_sdk_sts::Client>,
+    cache: web::ThinData<BackendCache>,
     path: web::Path<(String, String)>,
     range: web::Query<RangeRequest>,
     request: HttpRequest,
     db: web::ThinData<MemoryDB>,
     config: web::Data<ConfigX402<&'static str>>,
+    limits: web::ThinData<RateLimits>,
+    settlements: web::ThinData<Settlements>,
     auth: Option<x402::PaymentExtractor>,
-) -> impl Responder {
+) -> Result<impl Responder, ApiError> {
     let url = request.full_url();
+    let has_payment_header = request.headers().contains_key(x402::PAYMENT_HEADER);
+
+    // Throttle clients before any lookup, those sending invalid payments the most
+    let ip = limits.client_ip(&request);
+    limits.check_ip(&ip)?;
+    limits.check_invalid_payments(&ip)?;
     let RangeRequest { offset, length } = range.into_inner();
 
-    let (pay_to, storage) = match db
-        .get_bucket(&path.0)
-        .and_then(|c| db.get_client(&c))
-        .map(|c| (c.vault, c.storage))
-    {
-        Ok((Some(vault), Some(s))) => (vault.to_string(), s),
-        Ok(_) => {
-            tracing::error!("Bucket owner not found");
-            panic!("Bucket owner not found");
-        }
+    let (bucket, object) = path.into_inner();
+    Span::current().record("object", &object);
+    let (owner, storage) = bucket_owner(&db, &bucket)?;
+    let Some(pay_to) = owner.vault.map(|v| v.to_string()) else {
+        tracing::error!(?bucket, "Bucket owner has no vault");
+        return Err(ApiError::from(ErrorCode::BucketOwnerNotFound));
+    };
+
+    // Resolve the object size before asking for a payment
+    let backend = storage_backend(&cache, &storage).await?;
+    let size = match backend.head_object(&bucket, &object).await {
+        Ok(info) => info.size,
         Err(error) => {
-            tracing::error!(?error, "Failed to get bucket owner");
-            panic!("Failed to get bucket owner");
+            tracing::warn!(?error, "Failed to get object info");
+            return Err(ApiError::from(ErrorCode::ObjectNotFound));
         }
     };
 
+    // Only charge for the bytes that can actually be delivered
+    let Some(length) = utils::clamp_range(offset, length, size) else {
+        tracing::warn!(offset, length, size, "Range not satisfiable");
+        return Err(ApiError::RangeNotSatisfiable { size });
+    };
+
     // Get the price in USDC / 1MB
-    let price = db.get_price(&path).unwrap_or(1000);
-    let total_price = utils::calculate_price(price as f32, length as f32).to_string();
+    let price = db
+        .get_price(&(bucket.clone(), object.clone()))
+        .unwrap_or(DEFAULT_PRICE);
+    let total_price = utils::calculate_price(price as f32, length as f32);
 
     // Check received payment
-    let req = x402::PaymentRequest::new(&config, pay_to, total_price, "Access the object", url);
+    let req = x402::PaymentRequest::new(
+        &config,
+        pay_to,
+        total_price.to_string(),
+        "Access the object",
+        url,
+    );
     let request = x402::X402Response::new(&[req]);
     let Some(payment) = auth else {
-        return ResultAPI::payment_required(request);
+        // A header that is present but cannot be decoded is rejected rather than challenged
+        if has_payment_header {
+            tracing::warn!("Invalid X-Payment header");
+            METRICS
+                .verifications
+                .with_label_values(&["malformed"])
+                .inc();
+            limits.invalid_payment(&ip)?;
+            return Err(ApiError::from(ErrorCode::InvalidPayment));
+        }
+        METRICS.challenges.with_label_values(&[&bucket]).inc();
+        return Err(ApiError::PaymentRequired(Box::new(request)));
     };
 
-    // Verify and settle the payment
+    // Verify the payment
+    let nonce = payment.payload.authorization.nonce.clone();
+    let mut record = Payment {
+        bucket: bucket.clone(),
+        object: object.clone(),
+        payer: payment.payload.authorization.from.clone(),
+        amount: payment.payload.authorization.value.clone(),
+        status: PaymentStatus::Pending,
+        reason: None,
+        settlement: None,
+    };
+    Span::current()
+        .record("payer", &record.payer)
+        .record("amount", &record.amount);
     let facilitator = x402::FacilitatorRequest::new(payment, request.accepts[0].clone());
     match facilitator.verify() {
         Ok(response) if response.is_valid() => {
-            tracing::info!(?response, "x402 Settlement started");
-            actix_web::rt::spawn(async move { facilitator.settle() });
+            tracing::info!(?response, "x402 Payment verified");
+            METRICS.verifications.with_label_values(&["valid"]).inc();
+            // The payer is only known once the facilitator checked the signature
+            limits.check_payer(&record.payer)?;
         }
         Ok(response) => {
             tracing::warn!(?response, "x402 Payment verification failed");
-            return ResultAPI::payment_required(request);
+            METRICS.verifications.with_label_values(&["invalid"]).inc();
+            limits.invalid_payment(&ip)?;
+            return Err(ApiError::PaymentRequired(Box::new(request)));
+        }
+        Err(error) if x402::is_rejection(&error) => {
+            tracing::warn!(?error, "x402 Payment rejected by the facilitator");
+            METRICS.verifications.with_label_values(&["invalid"]).inc();
+            limits.invalid_payment(&ip)?;
+            return Err(ApiError::PaymentRequired(Box::new(request)));
         }
         Err(error) => {
+            // The payment may well be valid, so the client retries rather than paying again
             tracing::error!(?error, "Failed to verify x402 payment");
-            return ResultAPI::payment_required(request);
+            METRICS.verifications.with_label_values(&["error"]).inc();
+            return Err(ApiError::new(
+                ErrorCode::FacilitatorError,
+                "Failed to verify the payment",
+            ));
         }
     }
 
-    // Get the range of the object
-    let (bucket, object) = path.into_inner();
-    let s3 = match XByteS3::new_assumed_role(
-        &sts,
-        storage.role_arn(),
-        "xbyte-s3",
-        storage.region().clone(),
-    )
-    .await
-    {
-        Ok(s3) => s3,
+    // Get the range of the object before settling, so failed deliveries are never charged
+    let data = match backend.read_range(&bucket, &object, offset, length).await {
+        Ok(stream) => storage::collect(stream).await,
+        Err(error) => Err(error),
+    };
+    let data = match data {
+        Ok(data) => data,
         Err(error) => {
-            tracing::error!(?error, "Failed to create S3 client");
-            return ResultAPI::payment_required(request);
+            tracing::error!(
+                ?error,
+                ?nonce,
+                "Failed to get object range, voiding payment"
+            );
+            record.status = PaymentStatus::Voided;
+            record.reason = Some(error.to_string());
+            if let Err(error) = db.record_payment(nonce, record) {
+                tracing::error!(?error, "Failed to record voided payment");
+            }
+            return Err(ApiError::new(
+                ErrorCode::StorageError,
+                "Failed to get object range",
+            ));
         }
     };
 
-    // Get the range of the object
-    match s3.get_range(&bucket, &object, offset, length).await {
-        Ok(data) => ResultAPI::okay(data.into_bytes()),
+    // Settle the payment
+    METRICS
+        .bytes_delivered
+        .with_label_values(&[&bucket])
+        .inc_by(data.len() as u64);
+    METRICS
+        .price_charged
+        .with_label_values(&[&bucket])
+        .inc_by(total_price);
+    record.settlement = match serde_json::to_value(&facilitator) {
+        Ok(request) => Some(request),
         Err(error) => {
-            tracing::error!(?error, "Failed to get object range");
-            ResultAPI::payment_required(request)
+            tracing::error!(?error, "Failed to serialize the settlement");
+            return Err(ApiError::new(
+                ErrorCode::InternalError,
+                "Failed to settle the payment",
+            ));
         }
-    }
+    };
+    // The settlement outlives the request but stays in its span, is drained on shutdown
+    // and retried on the next start when it did not complete
+    tracing::info!(?nonce, "x402 Settlement started");
+    settlements.settle(&db, nonce, record);
+
+    Ok(ResultAPI::success(data))
 }
 
 /// The request to register a bucket
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RegisterRequest {
     /// The storage information
     pub storage: Storage<String>,
     /// The client ID
+    #[schema(value_type = String)]
     pub client: alloy_primitives::Address,
+    /// The buckets to register
+    pub buckets: Vec<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/s3/register",
+    tag = "s3",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "The buckets are registered", body = String),
+        (status = 400, description = "No buckets or they are not accessible", body = ErrorBody),
+        (status = 409, description = "A bucket belongs to another client", body = ErrorBody),
+    )
+)]
 #[post("/s3/register")]
 async fn register_bucket(
     db: web::ThinData<MemoryDB>,
-    sts: web::ThinData<aws_sdk_sts::Client>,
+    cache: web::ThinData<BackendCache>,
     web::Json(payload): web::Json<RegisterRequest>,
-) -> impl Responder {
-    let s3 = match XByteS3::new_assumed_role(
-        &sts,
-        payload.storage.role_arn(),
-        "xbyte",
-        payload.storage.region().clone(),
-    )
-    .await
-    {
-        Ok(s3) => s3,
-        Err(error) => {
-            tracing::error!(?error, "Failed to create S3 client");
-            return ResultAPI::failure("Failed to create S3 client");
-        }
-    };
+) -> Result<impl Responder, ApiError> {
+    register_buckets(&db, &cache, payload).await?;
+    Ok(ResultAPI::success("Storage registered successfully"))
+}
 
-    // Map bucket to client
-    let buckets = match s3.list_buckets().await {
-        Ok(b) => b,
-        Err(error) => {
-            tracing::error!(?error, "Failed to list buckets");
-            return ResultAPI::failure("Failed to list buckets");
+/// Assign the storage and buckets to the client, once every bucket is found accessible
+pub async fn register_buckets(
+    db: &MemoryDB,
+    cache: &BackendCache,
+    payload: RegisterRequest,
+) -> Result<(), ApiError> {
+    if payload.buckets.is_empty() {
+        tracing::warn!(?payload.client, "No buckets to register");
+        return Err(ApiError::from(ErrorCode::NoBuckets));
+    }
+
+    // Reject buckets already owned by another client
+    for bucket in &payload.buckets {
+        match db.get_bucket(bucket) {
+            Ok(owner) if owner != payload.client => {
+                tracing::warn!(?bucket, ?owner, "Bucket already registered");
+                let details = serde_json::json!({ "bucket": bucket });
+                return Err(
+                    ApiError::from(ErrorCode::BucketAlreadyRegistered).with_details(details)
+                );
+            }
+            _ => (),
         }
-    };
+    }
 
-    for bucket in buckets {
-        if let Err(error) = db.assign_bucket(bucket.name.unwrap(), payload.client) {
-            tracing::error!(?error, "Failed to assign bucket to client");
-            return ResultAPI::failure("Failed to assign bucket to client");
+    let backend = storage_backend(cache, &payload.storage).await?;
+
+    // Validate access to every bucket through the storage credentials
+    for bucket in &payload.buckets {
+        if let Err(error) = backend.head_container(bucket).await {
+            tracing::error!(?error, ?bucket, "Bucket is not accessible");
+            let details = serde_json::json!({ "bucket": bucket });
+            return Err(ApiError::from(ErrorCode::BucketNotAccessible).with_details(details));
         }
     }
 
+    // Drop cached credentials of the storage being replaced
+    if let Ok(previous) = db.get_storage(&payload.client)
+        && previous != payload.storage
+    {
+        cache.evict(&previous);
+    }
+
     // Assign storage to client
     if let Err(error) = db.assign_storage(payload.client, payload.storage) {
         tracing::error!(?error, "Failed to register storage");
-        return ResultAPI::failure("Failed to register storage");
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            "Failed to register storage",
+        ));
     };
 
-    ResultAPI::okay("Storage registered successfully")
+    // Map bucket to client
+    for bucket in payload.buckets {
+        if let Err(error) = db.assign_bucket(bucket, payload.client) {
+            tracing::error!(?error, "Failed to assign bucket to client");
+            return Err(ApiError::new(
+                ErrorCode::InternalError,
+                "Failed to assign bucket to client",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The request to unregister a bucket
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct UnregisterRequest {
+    /// The client owning the bucket
+    #[param(value_type = String)]
+    pub client: alloy_primitives::Address,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/s3/bucket/{bucket}",
+    tag = "s3",
+    params(("bucket" = String, Path, description = "The bucket"), UnregisterRequest),
+    responses(
+        (status = 200, description = "The bucket is unregistered", body = String),
+        (status = 403, description = "The bucket is owned by another client", body = ErrorBody),
+        (status = 404, description = "The bucket is not registered", body = ErrorBody),
+    )
+)]
+#[delete("/s3/bucket/{bucket}")]
+async fn unregister_bucket(
+    bucket: web::Path<String>,
+    query: web::Query<UnregisterRequest>,
+    db: web::ThinData<MemoryDB>,
+) -> Result<impl Responder, ApiError> {
+    // Only the owner may unregister its bucket
+    match db.get_bucket(&bucket) {
+        Ok(owner) if owner == query.client => {}
+        Ok(owner) => {
+            tracing::warn!(?bucket, ?owner, client = ?query.client, "Bucket owned by another client");
+            return Err(ApiError::from(ErrorCode::BucketNotOwned));
+        }
+        Err(error) => {
+            tracing::error!(?error, ?bucket, "Failed to unregister bucket");
+            return Err(ApiError::from(ErrorCode::BucketNotFound));
+        }
+    }
+
+    // Remove the bucket mapping and its prices
+    match db.remove_bucket(&bucket) {
+        Ok(_) => Ok(ResultAPI::success("Bucket unregistered successfully")),
+        Err(error) => {
+            tracing::error!(?error, ?bucket, "Failed to unregister bucket");
+            Err(ApiError::from(ErrorCode::BucketNotFound))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorBody;
+    use crate::RateLimitConfig;
+    use crate::storage::tests::offline_sts;
+    use crate::storage::tests::temp_storage;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{CONTENT_RANGE, RETRY_AFTER};
+    use actix_web::{App, test, web::ThinData};
+    use alloy_primitives::address;
+    use base64::Engine;
+
+    /// Register a client serving the `media` bucket from a local directory, returned with it
+    fn local_client(db: &MemoryDB) -> anyhow::Result<(BackendCache, String)> {
+        let storage = temp_storage()?;
+        let root = storage.root().to_string_lossy().into_owned();
+        let wallet = address!("0xc0ffee1234567890123456789012345678901234");
+        let client = Client::new(String::from("platformA"), wallet);
+
+        db.set_client(wallet, client)?;
+        db.assign_storage(wallet, Storage::Local { root: root.clone() })?;
+        db.assign_bucket(String::from("media"), wallet)?;
+
+        let cache = BackendCache::new(offline_sts()).with_local_root(&root);
+        Ok((cache, root))
+    }
+
+    #[actix_web::test]
+    async fn test_get_all_buckets_shared_storage() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let cache = ThinData(cache);
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .service(get_all_buckets);
+        let server = test::init_service(app).await;
+
+        // A second client registers another bucket of the same storage
+        std::fs::create_dir_all(std::path::Path::new(&root).join("music"))?;
+        let wallet = address!("0xbadbadbadbadbadbadbadbadbadbadbadbadbad0");
+        db.set_client(wallet, Client::new(String::from("platformB"), wallet))?;
+        db.assign_storage(wallet, Storage::Local { root: root.clone() })?;
+        db.assign_bucket(String::from("music"), wallet)?;
+
+        // Each bucket is listed once, under its owner only
+        let req = test::TestRequest::get().uri("/s3/bucket").to_request();
+        let res: ResultAPI<Vec<String>, ()> = test::call_and_read_body_json(&server, req).await;
+        let mut buckets = res.get_data().unwrap().clone();
+        buckets.sort();
+        assert_eq!(buckets, ["media", "music"]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_all_objects_local() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let cache = ThinData(cache);
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .service(get_all_objects);
+        let server = test::init_service(app).await;
+
+        // Request & Response
+        db.set_price((String::from("media"), String::from("song.mp3")), 2000)?;
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/objects")
+            .to_request();
+        let res: ResultAPI<ObjectListing, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        let listing = res.get_data().unwrap();
+        let keys = listing.objects.iter().map(|o| o.info.key.as_str());
+        assert_eq!(
+            keys.collect::<Vec<_>>(),
+            vec!["song.mp3", "videos/movie.mp4"]
+        );
+        assert_eq!(listing.objects[0].price, 2000);
+        assert_eq!(listing.objects[0].info.size, 11);
+        assert_eq!(listing.objects[1].price, DEFAULT_PRICE);
+        assert_eq!(listing.next_cursor, None);
+
+        // Paginate through the bucket with a prefix and delimiter
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/objects?delimiter=/&limit=1")
+            .to_request();
+        let res: ResultAPI<ObjectListing, ()> = test::call_and_read_body_json(&server, req).await;
+        let listing = res.get_data().unwrap();
+        assert_eq!(listing.objects[0].info.key, "song.mp3");
+        assert_eq!(listing.next_cursor.as_deref(), Some("song.mp3"));
+
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/objects?delimiter=/&limit=1&cursor=song.mp3")
+            .to_request();
+        let res: ResultAPI<ObjectListing, ()> = test::call_and_read_body_json(&server, req).await;
+        let listing = res.get_data().unwrap();
+        assert!(listing.objects.is_empty());
+        assert_eq!(listing.prefixes, vec!["videos/"]);
+        assert_eq!(listing.next_cursor, None);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_object_payment_required() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let cache = ThinData(cache);
+        let config = web::Data::new(ConfigX402::build());
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .app_data(config)
+            .app_data(ThinData(RateLimits::default()))
+            .app_data(ThinData(Settlements::default()))
+            .service(get_object);
+        let server = test::init_service(app).await;
+
+        // Request & Response
+        db.set_price((String::from("media"), String::from("song.mp3")), 2_097_152)?;
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/song.mp3?offset=0&length=1024")
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+
+        // Verify the payment request, charged on the 11 deliverable bytes
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["accepts"][0]["maxAmountRequired"], "22");
+
+        // Ranges starting past the end are rejected before any payment
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/song.mp3?offset=11&length=1")
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers().get(CONTENT_RANGE).unwrap(), "bytes */11");
+
+        // Undecodable payments are rejected instead of challenged
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/song.mp3?offset=0&length=1024")
+            .insert_header((x402::PAYMENT_HEADER, "not-base64"))
+            .to_request();
+        let res: ResultAPI<(), ErrorBody> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.get_error().unwrap().code, ErrorCode::InvalidPayment);
+
+        // Empty ranges are rejected as well
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/song.mp3?offset=0&length=0")
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_object_rate_limited() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let cache = ThinData(cache);
+        let limits = RateLimitConfig {
+            per_ip: 5,
+            invalid_payment: 2,
+            ..Default::default()
+        };
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .app_data(web::Data::new(ConfigX402::build()))
+            .app_data(ThinData(RateLimits::new(&limits)))
+            .app_data(ThinData(Settlements::default()))
+            .service(get_object);
+        let server = test::init_service(app).await;
+        let request = |ip: &str, payment: Option<&str>| {
+            let req = test::TestRequest::get()
+                .uri("/s3/bucket/media/object/song.mp3?offset=0&length=1024")
+                .peer_addr(format!("{ip}:4000").parse().unwrap());
+            match payment {
+                Some(payment) => req.insert_header((x402::PAYMENT_HEADER, payment)),
+                None => req,
+            }
+            .to_request()
+        };
+
+        // Invalid payments are limited more strictly than challenges
+        for _ in 0..2 {
+            let res = test::call_service(&server, request("10.0.0.1", Some("bad"))).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        let res = test::call_service(&server, request("10.0.0.1", Some("bad"))).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(RETRY_AFTER));
+
+        // The client stays blocked even without a payment, others are unaffected
+        let res = test::call_service(&server, request("10.0.0.1", None)).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let res = test::call_service(&server, request("10.0.0.2", None)).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+
+        // Challenges are limited per IP
+        for _ in 0..4 {
+            let res = test::call_service(&server, request("10.0.0.2", None)).await;
+            assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        }
+        let res = test::call_service(&server, request("10.0.0.2", None)).await;
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .unwrap()
+            .to_str()?
+            .parse::<u64>()?;
+        assert_eq!(retry_after, 12);
+        let body: ResultAPI<(), ErrorBody> = test::read_body_json(res).await;
+        assert_eq!(body.get_error().unwrap().code, ErrorCode::RateLimited);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_forged_payment_keeps_payer_budget() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let limits = RateLimits::new(&RateLimitConfig {
+            per_payer: 1,
+            ..Default::default()
+        });
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(ThinData(cache))
+            .app_data(web::Data::new(ConfigX402::build()))
+            .app_data(ThinData(limits.clone()))
+            .app_data(ThinData(Settlements::default()))
+            .service(get_object);
+        let server = test::init_service(app).await;
+
+        // A payment claiming to come from someone else, with a forged signature
+        let victim = "0x1234567890AbcdEF1234567890aBcdef12345678";
+        let payment = serde_json::json!({
+            "x402Version": 1,
+            "scheme": "exact",
+            "network": "base-sepolia",
+            "payload": {
+                "signature": "0x00",
+                "authorization": {
+                    "from": victim,
+                    "to": ConfigX402::build().payment_address,
+                    "value": "1000",
+                    "validAfter": "0",
+                    "validBefore": "9999999999",
+                    "nonce": "0x01",
+                },
+            },
+        });
+        let payment = base64::engine::general_purpose::STANDARD.encode(payment.to_string());
+        for _ in 0..3 {
+            let req = test::TestRequest::get()
+                .uri("/s3/bucket/media/object/song.mp3?offset=0&length=1024")
+                .insert_header((x402::PAYMENT_HEADER, payment.as_str()))
+                .to_request();
+            let res = test::call_service(&server, req).await;
+            assert_ne!(res.status(), StatusCode::OK);
+            assert_ne!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+
+        // The unverified payments never spent the budget of the payer they named
+        assert!(limits.check_payer(victim).is_ok());
+        assert!(limits.check_payer(victim).is_err());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_object_info() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let (cache, root) = local_client(&db)?;
+        let cache = ThinData(cache);
+        let app = App::new()
+            .app_data(db.clone())
+            .app_data(cache)
+            .service(get_object)
+            .service(get_object_info)
+            .service(head_object);
+        let server = test::init_service(app).await;
+
+        // Request & Response
+        db.set_price((String::from("media"), String::from("song.mp3")), 1_048_576)?;
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/song.mp3/info")
+            .to_request();
+        let res: ResultAPI<ObjectDetails, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        let details = res.get_data().unwrap();
+        assert_eq!(details.info.size, 11);
+        assert_eq!(details.info.content_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(details.price, 1_048_576);
+        assert_eq!(details.total_price, 11);
+
+        // The same metadata as headers, without payment
+        let req = test::TestRequest::default()
+            .method(actix_web::http::Method::HEAD)
+            .uri("/s3/bucket/media/object/song.mp3")
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(OBJECT_SIZE_HEADER).unwrap(), "11");
+        assert_eq!(res.headers().get(TOTAL_PRICE_HEADER).unwrap(), "11");
+        assert!(res.headers().contains_key("ETag"));
+
+        // Unknown object
+        let req = test::TestRequest::get()
+            .uri("/s3/bucket/media/object/missing.mp3/info")
+            .to_request();
+        let res: ResultAPI<(), ErrorBody> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.get_error().unwrap().code, ErrorCode::ObjectNotFound);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_unregister_bucket_api() -> anyhow::Result<()> {
+        // Run the server
+        let db = ThinData(MemoryDB::default());
+        let app = App::new().app_data(db.clone()).service(unregister_bucket);
+        let server = test::init_service(app).await;
+
+        // Insert to DB
+        let wallet = address!("0xc0ffee1234567890123456789012345678901234");
+        let other = address!("0xbadbadbadbadbadbadbadbadbadbadbadbadbad0");
+        let price_key = (String::from("bucket"), String::from("object"));
+        db.assign_bucket(String::from("bucket"), wallet)?;
+        db.set_price(price_key.clone(), 1000)?;
+
+        // Another client cannot remove the bucket
+        let req = test::TestRequest::delete()
+            .uri(&format!("/s3/bucket/bucket?client={other}"))
+            .to_request();
+        let res: ResultAPI<(), ErrorBody> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::FORBIDDEN);
+        assert_eq!(res.get_error().unwrap().code, ErrorCode::BucketNotOwned);
+        assert_eq!(db.get_bucket(&String::from("bucket"))?, wallet);
+        assert_eq!(db.get_price(&price_key)?, 1000);
+
+        // Request & Response
+        let req = test::TestRequest::delete()
+            .uri(&format!("/s3/bucket/bucket?client={wallet}"))
+            .to_request();
+        let res: ResultAPI<String, ()> = test::call_and_read_body_json(&server, req).await;
+        assert_eq!(res.get_status(), StatusCode::OK);
+
+        // Verify the data
+        assert!(db.get_bucket(&String::from("bucket")).is_err());
+        assert!(db.get_price(&price_key).is_err());
+
+        // Unknown bucket
+        let req = test::TestRequest::delete()
+            .uri(&format!("/s3/bucket/bucket?client={wallet}"))
+            .to_request();
+        let res = test::call_service(&server, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
 }
@@ -1,10 +1,14 @@
+use crate::propagation::TraceInterceptor;
+use crate::storage::{self, ByteStream, ListQuery, ObjectInfo, ObjectPage, StorageBackend};
 use crate::utils;
 use aws_config::{Region, SdkConfig};
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Credentials;
 use aws_sdk_s3::primitives::AggregatedBytes;
+use aws_sdk_s3::primitives::DateTimeFormat as Format;
 use aws_sdk_s3::types::{Bucket, Object};
 use aws_sdk_sts::Client as StsClient;
+use futures_util::stream;
 use std::borrow::Cow;
 use std::time::SystemTime;
 
@@ -16,7 +20,7 @@ impl XByteS3 {
     /// Create a new xByte S3 client
     pub async fn new() -> Self {
         let config = aws_config::load_from_env().await;
-        Self(Client::new(&config))
+        Self::from(&config)
     }
 
     /// Create a new xByte S3 client by assuming a role in another account/org
@@ -26,6 +30,17 @@ impl XByteS3 {
         session_name: &str,
         region: impl Into<Cow<'static, str>>,
     ) -> anyhow::Result<Self> {
+        let (s3, _) = Self::assume_role(sts_client, role_arn, session_name, region).await?;
+        Ok(s3)
+    }
+
+    /// Assume a role and return the client along with its credentials expiration
+    pub async fn assume_role(
+        sts_client: &StsClient,
+        role_arn: &str,
+        session_name: &str,
+        region: impl Into<Cow<'static, str>>,
+    ) -> anyhow::Result<(Self, SystemTime)> {
         let assumed_role = sts_client
             .assume_role()
             .role_arn(role_arn)
@@ -37,20 +52,43 @@ impl XByteS3 {
             return Err(anyhow::anyhow!("no credentials returned from assume role"));
         };
 
+        let expiration = SystemTime::try_from(cred.expiration)?;
         let credentials = Credentials::new(
             cred.access_key_id,
             cred.secret_access_key,
             Some(cred.session_token),
-            Some(SystemTime::try_from(cred.expiration)?),
+            Some(expiration),
             "assumed-role",
         );
 
         let config = aws_sdk_s3::Config::builder()
             .credentials_provider(credentials)
             .region(Region::new(region))
+            .interceptor(TraceInterceptor)
+            .build();
+
+        Ok((Self(Client::from_conf(config)), expiration))
+    }
+
+    /// Create a new xByte S3 client for an S3-compatible endpoint with static credentials
+    pub fn new_static(
+        endpoint: &str,
+        region: impl Into<Cow<'static, str>>,
+        access_key_id: &str,
+        secret_access_key: &str,
+        path_style: bool,
+    ) -> Self {
+        let credentials = Credentials::new(access_key_id, secret_access_key, None, None, "static");
+
+        let config = aws_sdk_s3::Config::builder()
+            .credentials_provider(credentials)
+            .region(Region::new(region))
+            .endpoint_url(endpoint)
+            .force_path_style(path_style)
+            .interceptor(TraceInterceptor)
             .build();
 
-        Ok(Self(Client::from_conf(config)))
+        Self(Client::from_conf(config))
     }
 
     /// Get a presigned request for a range of a file
@@ -86,18 +124,146 @@ impl XByteS3 {
         Ok(buckets)
     }
 
-    /// List all objects in a bucket
+    /// Check that the bucket exists and is accessible
+    pub async fn head_bucket(&self, bucket: &str) -> anyhow::Result<()> {
+        self.0.head_bucket().bucket(bucket).send().await?;
+        Ok(())
+    }
+
+    /// List all objects in a bucket, following continuation tokens
     pub async fn list_objects(&self, bucket: &str) -> anyhow::Result<Vec<Object>> {
-        let req = self.0.list_objects().bucket(bucket).send().await?;
-        let objects = req.contents.ok_or(anyhow::anyhow!("no objects found"))?;
+        let pages = self
+            .0
+            .list_objects_v2()
+            .bucket(bucket)
+            .into_paginator()
+            .send()
+            .try_collect()
+            .await?;
 
-        Ok(objects)
+        Ok(pages
+            .into_iter()
+            .flat_map(|p| p.contents.unwrap_or_default())
+            .collect())
+    }
+}
+
+impl StorageBackend for XByteS3 {
+    async fn list_containers(&self) -> anyhow::Result<Vec<String>> {
+        let buckets = self.list_buckets().await?;
+        Ok(buckets.into_iter().filter_map(|b| b.name).collect())
+    }
+
+    async fn head_container(&self, container: &str) -> anyhow::Result<()> {
+        self.head_bucket(container).await
+    }
+
+    async fn list_objects(&self, container: &str, query: &ListQuery) -> anyhow::Result<ObjectPage> {
+        let page = self
+            .0
+            .list_objects_v2()
+            .bucket(container)
+            .set_prefix(query.prefix().map(str::to_string))
+            .set_delimiter(query.delimiter().map(str::to_string))
+            .set_continuation_token(query.cursor.clone())
+            .max_keys(query.limit().try_into()?)
+            .send()
+            .await?;
+
+        // Listings carry no content type, so it is guessed from the key
+        let objects = page
+            .contents
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|o| {
+                let key = o.key?;
+                Some(ObjectInfo {
+                    size: o.size.unwrap_or_default().try_into().unwrap_or_default(),
+                    last_modified: o.last_modified.and_then(|d| d.fmt(Format::DateTime).ok()),
+                    etag: o.e_tag,
+                    content_type: storage::guess_content_type(&key),
+                    key,
+                })
+            })
+            .collect();
+        let prefixes = page
+            .common_prefixes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|p| p.prefix)
+            .collect();
+        let next_cursor = match page.is_truncated {
+            Some(true) => page.next_continuation_token,
+            _ => None,
+        };
+
+        Ok(ObjectPage {
+            objects,
+            prefixes,
+            next_cursor,
+        })
+    }
+
+    async fn head_object(&self, container: &str, key: &str) -> anyhow::Result<ObjectInfo> {
+        let head = self
+            .0
+            .head_object()
+            .bucket(container)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: head.content_length.unwrap_or_default().try_into()?,
+            last_modified: head
+                .last_modified
+                .and_then(|d| d.fmt(Format::DateTime).ok()),
+            etag: head.e_tag,
+            content_type: head.content_type,
+        })
+    }
+
+    async fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<ByteStream> {
+        let range =
+            utils::calculate_range_header(offset, len).ok_or(anyhow::anyhow!("range overflow"))?;
+
+        let req = self
+            .0
+            .get_object()
+            .bucket(container)
+            .key(key)
+            .range(range)
+            .send()
+            .await?;
+
+        let stream = stream::try_unfold(req.body, |mut body| async move {
+            let chunk = body.try_next().await?;
+            anyhow::Ok(chunk.map(|c| (c, body)))
+        });
+
+        Ok(Box::pin(stream))
     }
 }
 
 impl From<&SdkConfig> for XByteS3 {
     fn from(config: &SdkConfig) -> Self {
-        Self(Client::new(config))
+        let config = aws_sdk_s3::config::Builder::from(config)
+            .interceptor(TraceInterceptor)
+            .build();
+        Self(Client::from_conf(config))
+    }
+}
+
+impl From<Client> for XByteS3 {
+    fn from(client: Client) -> Self {
+        Self(client)
     }
 }
 
@@ -175,4 +341,67 @@ mod tests {
 
         Ok(())
     }
+
+    /// Create an S3-compatible client from the environment, e.g. a local MinIO container
+    fn s3_compatible_from_env() -> Option<XByteS3> {
+        let endpoint = std::env::var("S3_COMPATIBLE_ENDPOINT").ok()?;
+        let access_key_id = std::env::var("S3_COMPATIBLE_ACCESS_KEY_ID").ok()?;
+        let secret_access_key = std::env::var("S3_COMPATIBLE_SECRET_ACCESS_KEY").ok()?;
+
+        let client = XByteS3::new_static(
+            &endpoint,
+            "us-east-1",
+            &access_key_id,
+            &secret_access_key,
+            true,
+        );
+        Some(client)
+    }
+
+    #[actix_web::test]
+    async fn test_s3_compatible_roundtrip() -> anyhow::Result<()> {
+        dotenv::dotenv().ok();
+        const BUCKET: &str = "xbyte-test";
+        const OBJECT: &str = "hello.txt";
+
+        let Some(client) = s3_compatible_from_env() else {
+            eprintln!("No S3-compatible endpoint configured, skipping test");
+            return Ok(());
+        };
+
+        // Seed the bucket, which may be left over from a previous run
+        let _ = client.0.create_bucket().bucket(BUCKET).send().await;
+        client
+            .0
+            .put_object()
+            .bucket(BUCKET)
+            .key(OBJECT)
+            .body(b"hello xbyte".to_vec().into())
+            .send()
+            .await?;
+
+        // Verify the data
+        client.head_bucket(BUCKET).await?;
+        let buckets = client.list_buckets().await?;
+        assert!(buckets.iter().any(|b| b.name() == Some(BUCKET)));
+        let objects = client.list_objects(BUCKET).await?;
+        assert!(objects.iter().any(|o| o.key() == Some(OBJECT)));
+        let data = client.get_range(BUCKET, OBJECT, 6, 5).await?;
+        assert_eq!(data.into_bytes().as_ref(), b"xbyte");
+
+        // Verify the storage backend
+        let info = client.head_object(BUCKET, OBJECT).await?;
+        assert_eq!(info.size, 11);
+        let stream = client.read_range(BUCKET, OBJECT, 0, 5).await?;
+        assert_eq!(crate::storage::collect(stream).await?.as_ref(), b"hello");
+        let query = ListQuery {
+            prefix: Some(String::from("hello")),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = StorageBackend::list_objects(&client, BUCKET, &query).await?;
+        assert_eq!(page.objects[0].key, OBJECT);
+        assert_eq!(page.objects[0].content_type.as_deref(), Some("text/plain"));
+        Ok(())
+    }
 }
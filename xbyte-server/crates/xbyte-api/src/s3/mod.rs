@@ -1,5 +1,6 @@
-mod api;
+pub(crate) mod api;
 mod client;
 
-pub use api::S3Route;
+pub(crate) use api::{OBJECT_SIZE_HEADER, OBJECT_TYPE_HEADER, PRICE_HEADER, TOTAL_PRICE_HEADER};
+pub use api::{RegisterRequest, S3Route, register_buckets};
 pub use client::XByteS3;
@@ -1,56 +1,273 @@
-use crate::{ClientRoute, ConfigX402, HealthRoute, MemoryDB, PricingRoute, S3Route};
-use actix_web::web::{Data, ThinData};
+use crate::config::{
+    AwsConfig, CorsConfig, DatabaseConfig, RateLimitConfig, ServerConfig, TlsConfig, X402Config,
+};
+use crate::cors::cors;
+use crate::metrics::{self, MetricsRoute};
+use crate::propagation::trace_headers;
+use crate::ratelimit::RateLimits;
+use crate::settlement::Settlements;
+use crate::tls::CertResolver;
+use crate::trace;
+use crate::version;
+use crate::{ApiError, BackendCache, ClientRoute, ConfigX402, HealthRoute, MemoryDB};
+use crate::{OpenApiRoute, PricingRoute, S3Route};
+use actix_web::middleware::from_fn;
+use actix_web::web::{self, Data, ThinData};
 use actix_web::{App, HttpServer};
-use std::net;
+use aws_config::Region;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// A server that can be used to start the API
-pub struct Server<A: net::ToSocketAddrs, R: AsRef<str>> {
-    /// The address to bind the server to
-    addr: A,
-    /// The RPC URL (e.g. ethereum, base, etc.)
-    rpc: R,
+pub struct Server {
+    /// The server configuration
+    config: ServerConfig,
 }
 
-impl<A: net::ToSocketAddrs, R: AsRef<str>> Server<A, R> {
-    /// Create a new server
-    pub fn new(addr: A, rpc: R) -> Self {
-        Self { addr, rpc }
+impl Server {
+    /// Create a new server with the default configuration
+    pub fn new(addr: impl Into<String>, rpc: impl Into<String>) -> Self {
+        Self::from_config(ServerConfig {
+            addr: addr.into(),
+            rpc_url: rpc.into(),
+            ..Default::default()
+        })
+    }
+
+    /// Create a new server from a configuration
+    pub fn from_config(config: ServerConfig) -> Self {
+        Self { config }
+    }
+
+    /// Allow clients to serve content from local directories below the root
+    pub fn with_local_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.config.local_root = Some(root.into());
+        self
+    }
+
+    /// Allow storages to use custom endpoints on the given origins, e.g. a MinIO server
+    pub fn with_storage_endpoints<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        endpoints: I,
+    ) -> Self {
+        self.config.storage_endpoints = endpoints.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the number of HTTP workers
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.config.workers = Some(workers);
+        self
+    }
+
+    /// Allow the given origins to call the client, pricing and registration routes
+    pub fn with_allowed_origins<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        origins: I,
+    ) -> Self {
+        self.config.cors = CorsConfig {
+            allowed_origins: origins.into_iter().map(Into::into).collect(),
+        };
+        self
+    }
+
+    /// Terminate TLS with the PEM certificate chain and key, reloaded when the files change
+    pub fn with_tls(mut self, cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        self.config.tls = Some(TlsConfig {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        });
+        self
+    }
+
+    /// Set the x402 payment requirements
+    pub fn with_x402(mut self, x402: X402Config) -> Self {
+        self.config.x402 = x402;
+        self
+    }
+
+    /// Set the database backend
+    pub fn with_database(mut self, database: DatabaseConfig) -> Self {
+        self.config.database = database;
+        self
+    }
+
+    /// Set the AWS SDK settings
+    pub fn with_aws(mut self, aws: AwsConfig) -> Self {
+        self.config.aws = aws;
+        self
+    }
+
+    /// Set the seconds in-flight responses, then pending settlements, get to finish on shutdown
+    pub fn with_shutdown_timeout(mut self, seconds: u64) -> Self {
+        self.config.shutdown_timeout = seconds;
+        self
+    }
+
+    /// Set the rate limits of the object routes
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.config.rate_limit = rate_limit;
+        self
+    }
+
+    /// The server configuration
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
     }
 
     /// Run the API server
+    ///
+    /// On SIGTERM or Ctrl-C the server stops accepting connections, then waits up to the
+    /// shutdown timeout for in-flight responses and again for pending settlements.
     pub async fn run(self) -> anyhow::Result<()> {
+        let config = self.config;
+        config.validate()?;
+
         // Initialize data
-        let provider = xbyte_evm::Client::new(self.rpc.as_ref())?;
-        let db = MemoryDB::default();
-        let config = Data::new(ConfigX402::build());
-        let aws_config = aws_config::load_from_env().await;
-        let sts = aws_sdk_sts::Client::new(&aws_config);
+        let provider = xbyte_evm::Client::with_headers(&config.rpc_url, trace_headers)?;
+        let db = match &config.database {
+            DatabaseConfig::Memory => MemoryDB::default(),
+            DatabaseConfig::File { path, .. } => MemoryDB::load(path)?,
+        };
+        let x402 = Data::new(ConfigX402::from(&config.x402));
+        let cache = BackendCache::from_config(&config).await;
+        let cors_config = config.cors.clone();
+        let limits = RateLimits::new(&config.rate_limit);
+        let settlements = Settlements::default();
+        let app_settlements = settlements.clone();
+
+        // Retry the settlements a previous shutdown left pending
+        let resumed = settlements.resume(&db)?;
+        if resumed > 0 {
+            tracing::info!(resumed, "Resuming pending settlements");
+        }
 
+        let app_db = db.clone();
         let app = move || {
             App::new()
-                .app_data(config.clone())
+                .app_data(x402.clone())
                 .app_data(ThinData(provider.clone()))
-                .app_data(ThinData(db.clone()))
-                .app_data(ThinData(sts.clone()))
-                // Health routes
-                .service(HealthRoute::Status)
-                .service(HealthRoute::Index)
-                // Pricing routes
-                .service(PricingRoute::SetPrice)
-                .service(PricingRoute::GetPrice)
-                // Client / Customer routes
-                .service(ClientRoute::CreateClient)
-                .service(ClientRoute::GetClient)
-                // S3 routes
-                .service(S3Route::GetAllBuckets)
-                .service(S3Route::GetAllObjects)
-                .service(S3Route::GetObject)
-                .service(S3Route::RegisterBucket)
-                .wrap(actix_cors::Cors::permissive())
+                .app_data(ThinData(app_db.clone()))
+                .app_data(ThinData(cache.clone()))
+                .app_data(ThinData(limits.clone()))
+                .app_data(ThinData(app_settlements.clone()))
+                .app_data(web::JsonConfig::default().error_handler(ApiError::invalid_request))
+                .app_data(web::QueryConfig::default().error_handler(ApiError::invalid_request))
+                .app_data(web::PathConfig::default().error_handler(ApiError::invalid_request))
+                .configure(|config| version::mount(config, infra_routes, routes))
+                .wrap(from_fn(metrics::track))
+                .wrap(cors(&cors_config))
+                .wrap(from_fn(trace::request_span))
+        };
+
+        let shutdown_timeout = Duration::from_secs(config.shutdown_timeout);
+        let mut server = HttpServer::new(app).shutdown_timeout(config.shutdown_timeout);
+        if let Some(workers) = config.workers {
+            server = server.workers(workers);
+        }
+
+        // Snapshot the database while running and once more after shutdown
+        let snapshots = match &config.database {
+            DatabaseConfig::File {
+                path,
+                snapshot_interval,
+            } => {
+                let interval = Duration::from_secs(*snapshot_interval);
+                Some(tokio::spawn(snapshot(db.clone(), path.clone(), interval)))
+            }
+            DatabaseConfig::Memory => None,
+        };
+
+        // Terminate TLS when configured, swapping the certificate as it is renewed
+        let (server, certificates) = match &config.tls {
+            Some(tls) => {
+                let resolver = Arc::new(CertResolver::new(tls)?);
+                let server =
+                    server.bind_rustls_0_23(&config.addr, resolver.clone().server_config()?)?;
+                (server, Some(tokio::spawn(resolver.watch())))
+            }
+            None => (server.bind(&config.addr)?, None),
         };
 
-        HttpServer::new(app).bind(self.addr)?.run().await?;
-        Ok(())
+        // SIGTERM stops accepting connections and gives in-flight responses the timeout
+        let result = server.run().await;
+        if let Some(certificates) = certificates {
+            certificates.abort();
+        }
+
+        // Then the settlements of the last payments get the timeout as well
+        let pending = settlements.drain(shutdown_timeout).await;
+        if !pending.is_empty() {
+            tracing::warn!(
+                ?pending,
+                "Shutting down with settlements in flight, they are retried on the next start"
+            );
+        }
+        if let Some(snapshots) = snapshots {
+            snapshots.abort();
+        }
+        if let DatabaseConfig::File { path, .. } = &config.database {
+            db.save(path)?;
+        }
+
+        Ok(result?)
+    }
+}
+
+/// Register the health, metrics and spec routes
+pub(crate) fn infra_routes(config: &mut web::ServiceConfig) {
+    // Health routes
+    for route in HealthRoute::ALL {
+        config.service(route);
+    }
+    for route in MetricsRoute::ALL {
+        config.service(route);
+    }
+    for route in OpenApiRoute::ALL {
+        config.service(route);
+    }
+}
+
+/// Register every route of the API
+pub(crate) fn routes(config: &mut web::ServiceConfig) {
+    // Pricing routes
+    for route in PricingRoute::ALL {
+        config.service(route);
+    }
+    // Client / Customer routes
+    for route in ClientRoute::ALL {
+        config.service(route);
+    }
+    // S3 routes
+    for route in S3Route::ALL {
+        config.service(route);
+    }
+}
+
+/// Load the AWS SDK configuration, overriding the environment with the given settings
+pub(crate) async fn aws_config(config: &AwsConfig) -> aws_config::SdkConfig {
+    let mut loader = aws_config::from_env();
+    if let Some(region) = &config.region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    if let Some(profile) = &config.profile {
+        loader = loader.profile_name(profile);
+    }
+
+    loader.load().await
+}
+
+/// Periodically write the database to its snapshot file
+async fn snapshot(db: MemoryDB, path: PathBuf, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        if let Err(error) = db.save(&path) {
+            tracing::error!(?error, "Failed to snapshot the database");
+        }
     }
 }
 
@@ -64,7 +281,29 @@ mod tests {
         let rpc = "http://localhost:8545";
         let server = Server::new(addr, rpc);
 
-        assert_eq!(server.addr, addr);
-        assert_eq!(server.rpc, rpc);
+        assert_eq!(server.config.addr, addr);
+        assert_eq!(server.config.rpc_url, rpc);
+    }
+
+    #[test]
+    fn test_builder() {
+        let server = Server::new("127.0.0.1:80", "http://localhost:8545")
+            .with_workers(2)
+            .with_shutdown_timeout(5)
+            .with_allowed_origins(["https://xbyte.sh"])
+            .with_rate_limit(RateLimitConfig {
+                per_ip: 0,
+                ..Default::default()
+            })
+            .with_database(DatabaseConfig::File {
+                path: PathBuf::from("xbyte.json"),
+                snapshot_interval: 10,
+            });
+
+        assert_eq!(server.config().workers, Some(2));
+        assert_eq!(server.config().shutdown_timeout, 5);
+        assert_eq!(server.config().cors.allowed_origins, ["https://xbyte.sh"]);
+        assert_eq!(server.config().rate_limit.per_ip, 0);
+        assert!(server.config().validate().is_ok());
     }
 }
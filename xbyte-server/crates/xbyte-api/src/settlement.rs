@@ -0,0 +1,304 @@
+use crate::metrics::METRICS;
+use crate::{Database, MemoryDB, Payment, PaymentStatus, x402};
+use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::Span;
+
+/// The threads settling payments, further settlements wait in the queue
+const SETTLEMENT_WORKERS: usize = 16;
+
+/// A settlement waiting for a worker
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The settlements running in the background, keyed by payment nonce
+///
+/// Settlements run on a fixed pool of threads rather than on the HTTP workers, so they
+/// outlive the workers stopped on shutdown and can be drained before the process exits.
+#[derive(Debug, Clone)]
+pub(crate) struct Settlements {
+    /// The queue of the workers
+    queue: Sender<Job>,
+    /// The nonces of the settlements queued or in flight
+    pending: Arc<Mutex<HashSet<String>>>,
+    /// Notified whenever a settlement completes
+    done: Arc<Notify>,
+}
+
+/// Removes a settlement from the pending ones once it completes, even if it panicked
+struct Completion {
+    settlements: Settlements,
+    nonce: String,
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        let mut pending = self.settlements.lock();
+        pending.remove(&self.nonce);
+        self.settlements.done.notify_waiters();
+    }
+}
+
+impl Default for Settlements {
+    fn default() -> Self {
+        Self::new(SETTLEMENT_WORKERS)
+    }
+}
+
+impl Settlements {
+    /// Start the given number of settlement workers
+    pub fn new(workers: usize) -> Self {
+        let (queue, jobs) = std::sync::mpsc::channel::<Job>();
+        let jobs = Arc::new(Mutex::new(jobs));
+        for _ in 0..workers {
+            let jobs = jobs.clone();
+            let thread = std::thread::Builder::new()
+                .name(String::from("settlement"))
+                .spawn(move || work(&jobs));
+            if let Err(error) = thread {
+                tracing::error!(?error, "Failed to start a settlement worker");
+            }
+        }
+
+        Self {
+            queue,
+            pending: Default::default(),
+            done: Default::default(),
+        }
+    }
+
+    /// The nonces of the settlements queued or in flight
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run the settlement of a payment in the background, within the current span
+    pub fn spawn(&self, nonce: String, settle: impl FnOnce() + Send + 'static) {
+        self.lock().insert(nonce.clone());
+        let completion = Completion {
+            settlements: self.clone(),
+            nonce,
+        };
+
+        let span = Span::current();
+        let job = Box::new(move || {
+            let _completion = completion;
+            span.in_scope(settle);
+        });
+        if self.queue.send(job).is_err() {
+            tracing::error!("Settlement workers stopped, the payment stays pending");
+        }
+    }
+
+    /// Settle a verified payment with the facilitator in the background, recording the outcome
+    ///
+    /// The payment is recorded as pending with its facilitator request first, so a settlement
+    /// interrupted by a shutdown is retried by `resume` on the next start.
+    pub fn settle(&self, db: &MemoryDB, nonce: String, record: Payment) {
+        if let Err(error) = db.record_payment(nonce.clone(), record.clone()) {
+            tracing::error!(?error, "Failed to record payment");
+        }
+
+        let db = db.clone();
+        self.spawn(nonce.clone(), move || settle(&db, nonce, record));
+    }
+
+    /// Retry the settlements of the payments left pending, returning how many were queued
+    pub fn resume(&self, db: &MemoryDB) -> anyhow::Result<usize> {
+        self.resume_with(db, settle)
+    }
+
+    /// Retry the pending settlements with the given settle function
+    fn resume_with(
+        &self,
+        db: &MemoryDB,
+        settle: fn(&MemoryDB, String, Payment),
+    ) -> anyhow::Result<usize> {
+        let pending = db
+            .get_all_payments()?
+            .into_iter()
+            .filter(|(_, p)| p.status == PaymentStatus::Pending && p.settlement.is_some());
+
+        let mut count = 0;
+        for (nonce, record) in pending {
+            tracing::info!(?nonce, "x402 Settlement resumed");
+            let db = db.clone();
+            self.spawn(nonce.clone(), move || settle(&db, nonce, record));
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// The nonces of the settlements queued or in flight
+    pub fn pending(&self) -> Vec<String> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Wait for the settlements queued or in flight, returning those left after the timeout
+    pub async fn drain(&self, timeout: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let done = self.done.notified();
+            tokio::pin!(done);
+            done.as_mut().enable();
+
+            let pending = self.pending();
+            if pending.is_empty() {
+                return pending;
+            }
+            if tokio::time::timeout_at(deadline, done).await.is_err() {
+                return self.pending();
+            }
+        }
+    }
+}
+
+/// Run the queued settlements until every queue handle is dropped
+fn work(jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok(job) = job else {
+            return;
+        };
+
+        // A panicking settlement must not take the worker down with it
+        if std::panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            tracing::error!("Settlement panicked");
+        }
+    }
+}
+
+/// Settle a payment with the facilitator and record the outcome
+fn settle(db: &MemoryDB, nonce: String, mut record: Payment) {
+    let Some(request) = record.settlement.take() else {
+        return;
+    };
+
+    let timer = METRICS.settlement_duration.start_timer();
+    let settlement = x402::settle(&request);
+    timer.observe_duration();
+
+    record.status = match settlement {
+        Ok(response) if response.is_valid() => PaymentStatus::Settled,
+        Ok(response) => {
+            tracing::warn!(?response, "x402 Settlement failed");
+            record.reason = response.error_reason;
+            PaymentStatus::Failed
+        }
+        Err(error) => {
+            tracing::error!(?error, "Failed to settle x402 payment");
+            record.reason = Some(error.to_string());
+            PaymentStatus::Failed
+        }
+    };
+    let outcome = match record.status {
+        PaymentStatus::Settled => "settled",
+        _ => "failed",
+    };
+    METRICS.settlements.with_label_values(&[outcome]).inc();
+    if let Err(error) = db.record_payment(nonce, record) {
+        tracing::error!(?error, "Failed to record payment");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_drain_settlements() {
+        let settlements = Settlements::default();
+        assert!(settlements.drain(Duration::ZERO).await.is_empty());
+
+        // A quick settlement is waited for
+        settlements.spawn("0x01".into(), || {
+            std::thread::sleep(Duration::from_millis(50))
+        });
+        assert_eq!(settlements.pending(), ["0x01"]);
+        assert!(settlements.drain(Duration::from_secs(5)).await.is_empty());
+
+        // A stuck settlement is reported once the deadline passes
+        let (release, stuck) = mpsc::channel::<()>();
+        settlements.spawn("0x02".into(), move || {
+            let _ = stuck.recv();
+        });
+        let pending = settlements.drain(Duration::from_millis(50)).await;
+        assert_eq!(pending, ["0x02"]);
+
+        // A panicking settlement is not left pending
+        release.send(()).unwrap();
+        settlements.spawn("0x03".into(), || panic!("facilitator exploded"));
+        assert!(settlements.drain(Duration::from_secs(5)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bounded_settlements() {
+        let settlements = Settlements::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        // A burst of settlements never runs on more than the workers
+        for i in 0..8 {
+            let (running, peak) = (running.clone(), peak.clone());
+            settlements.spawn(format!("0x{i:02}"), move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        assert!(settlements.drain(Duration::from_secs(5)).await.is_empty());
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resume_settlements() -> anyhow::Result<()> {
+        let db = MemoryDB::default();
+        let payment = Payment {
+            bucket: String::from("media"),
+            object: String::from("song.mp3"),
+            payer: String::from("0x1234567890123456789012345678901234567890"),
+            amount: String::from("1000"),
+            status: PaymentStatus::Pending,
+            reason: None,
+            settlement: Some(serde_json::json!({ "x402Version": 1 })),
+        };
+        db.record_payment(String::from("0x01"), payment.clone())?;
+        let voided = Payment {
+            status: PaymentStatus::Voided,
+            ..payment.clone()
+        };
+        db.record_payment(String::from("0x02"), voided)?;
+
+        // A settlement outliving the shutdown keeps its retryable record
+        let settlements = Settlements::new(1);
+        let (release, stuck) = mpsc::channel::<()>();
+        settlements.spawn(String::from("0x01"), move || {
+            let _ = stuck.recv();
+        });
+        assert_eq!(settlements.drain(Duration::from_millis(50)).await, ["0x01"]);
+        assert_eq!(db.get_payment(&String::from("0x01"))?, payment);
+        release.send(())?;
+
+        // The next start settles it again, and only it
+        fn settled(db: &MemoryDB, nonce: String, mut record: Payment) {
+            assert!(record.settlement.take().is_some());
+            record.status = PaymentStatus::Settled;
+            db.record_payment(nonce, record).unwrap();
+        }
+        let settlements = Settlements::new(1);
+        assert_eq!(settlements.resume_with(&db, settled)?, 1);
+        assert!(settlements.drain(Duration::from_secs(5)).await.is_empty());
+        let payment = db.get_payment(&String::from("0x01"))?;
+        assert_eq!(payment.status, PaymentStatus::Settled);
+        assert_eq!(payment.settlement, None);
+        assert_eq!(settlements.resume_with(&db, settled)?, 0);
+        Ok(())
+    }
+}
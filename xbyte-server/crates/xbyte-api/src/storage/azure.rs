@@ -0,0 +1,518 @@
+use crate::storage::{ByteStream, ListQuery, ObjectInfo, ObjectPage, StorageBackend};
+use crate::utils;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat as Format};
+use base64::{Engine, engine::general_purpose};
+use futures_util::TryStreamExt;
+use hmac::{Hmac, Mac};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, HeaderMap, LAST_MODIFIED};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::time::SystemTime;
+use url::Url;
+
+/// The Blob service REST API version
+const API_VERSION: &str = "2021-08-06";
+
+/// A page of a Blob service list response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EnumerationResults {
+    containers: Option<Containers>,
+    blobs: Option<Blobs>,
+    next_marker: Option<String>,
+}
+
+/// The containers of a list response
+#[derive(Debug, Deserialize)]
+struct Containers {
+    #[serde(rename = "Container", default)]
+    items: Vec<Container>,
+}
+
+/// A container of a list response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Container {
+    name: String,
+}
+
+/// The blobs of a list response
+#[derive(Debug, Deserialize)]
+struct Blobs {
+    #[serde(rename = "Blob", default)]
+    items: Vec<Blob>,
+    #[serde(rename = "BlobPrefix", default)]
+    prefixes: Vec<BlobPrefix>,
+}
+
+/// A common prefix of a list response grouped by the delimiter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlobPrefix {
+    name: String,
+}
+
+/// A blob of a list response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Blob {
+    name: String,
+    properties: BlobProperties,
+}
+
+/// The properties of a blob
+#[derive(Debug, Deserialize)]
+struct BlobProperties {
+    #[serde(rename = "Last-Modified")]
+    last_modified: Option<String>,
+    #[serde(rename = "Etag")]
+    etag: Option<String>,
+    #[serde(rename = "Content-Length", default)]
+    content_length: u64,
+    #[serde(rename = "Content-Type")]
+    content_type: Option<String>,
+}
+
+impl From<Blob> for ObjectInfo {
+    fn from(blob: Blob) -> Self {
+        Self {
+            key: blob.name,
+            size: blob.properties.content_length,
+            last_modified: blob.properties.last_modified.and_then(http_date_to_rfc3339),
+            etag: blob.properties.etag,
+            content_type: blob.properties.content_type,
+        }
+    }
+}
+
+/// A storage backend for Azure Blob Storage
+#[derive(Debug, Clone)]
+pub struct AzureBlobStorage {
+    /// The HTTP client
+    http: reqwest::Client,
+    /// The Blob service endpoint
+    endpoint: Url,
+    /// The storage account name
+    account: String,
+    /// The decoded shared key of the account
+    access_key: Option<Vec<u8>>,
+    /// The shared access signature token
+    sas_token: Option<String>,
+}
+
+impl AzureBlobStorage {
+    /// Create a new Azure Blob storage, authorized by shared key or SAS token
+    pub fn new(
+        account: &str,
+        access_key: Option<&str>,
+        sas_token: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        // The account is part of the default host, so it must not be able to change it
+        let valid = (3..=24).contains(&account.len())
+            && account
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit());
+        if !valid {
+            return Err(anyhow::anyhow!("invalid storage account name {account:?}"));
+        }
+
+        let endpoint = match endpoint {
+            Some(e) => Url::parse(e)?,
+            None => Url::parse(&format!("https://{account}.blob.core.windows.net"))?,
+        };
+        let access_key = access_key
+            .map(|k| general_purpose::STANDARD.decode(k))
+            .transpose()?;
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            endpoint,
+            account: account.to_string(),
+            access_key,
+            sas_token: sas_token.map(|t| t.trim_start_matches('?').to_string()),
+        })
+    }
+
+    /// Build a URL below the endpoint, keeping `/` in blob names
+    fn url(&self, container: Option<&str>, blob: Option<&str>) -> anyhow::Result<Url> {
+        let mut url = self.endpoint.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| anyhow::anyhow!("invalid Azure endpoint"))?;
+            segments.pop_if_empty();
+            segments.extend(container);
+            segments.extend(blob.into_iter().flat_map(|b| b.split('/')));
+        }
+
+        Ok(url)
+    }
+
+    /// Send an authorized request, failing on error statuses
+    async fn send(
+        &self,
+        method: Method,
+        mut url: Url,
+        range: Option<String>,
+        body: Option<(&'static str, Vec<u8>)>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let date = DateTime::from(SystemTime::now()).fmt(Format::HttpDate)?;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ms-date", date.parse()?);
+        headers.insert("x-ms-version", API_VERSION.parse()?);
+        if let Some(range) = range {
+            headers.insert("x-ms-range", range.parse()?);
+        }
+        if let Some((content_type, data)) = &body {
+            headers.insert(CONTENT_TYPE, content_type.parse()?);
+            headers.insert(CONTENT_LENGTH, data.len().into());
+            headers.insert("x-ms-blob-type", "BlockBlob".parse()?);
+        }
+
+        if let Some(key) = &self.access_key {
+            let string_to_sign = string_to_sign(&method, &url, &headers, &self.account);
+            let signature = sign(key, &string_to_sign)?;
+            let authorization = format!("SharedKey {}:{signature}", self.account);
+            headers.insert("authorization", authorization.parse()?);
+        } else if let Some(sas) = &self.sas_token {
+            let query = match url.query() {
+                Some(q) => format!("{q}&{sas}"),
+                None => sas.clone(),
+            };
+            url.set_query(Some(&query));
+        }
+
+        let mut request = self.http.request(method, url).headers(headers);
+        if let Some((_, data)) = body {
+            request = request.body(data);
+        }
+
+        let request = crate::trace_headers()
+            .into_iter()
+            .fold(request, |request, (name, value)| {
+                request.header(name, value)
+            });
+        let response = request.send().await?;
+        match response.status() {
+            StatusCode::RANGE_NOT_SATISFIABLE => Err(anyhow::anyhow!("range not satisfiable")),
+            _ => Ok(response.error_for_status()?),
+        }
+    }
+
+    /// Fetch every page of a list endpoint
+    async fn list_all(&self, url: Url) -> anyhow::Result<Vec<EnumerationResults>> {
+        let mut pages = Vec::new();
+        let mut marker = None::<String>;
+        loop {
+            let mut page_url = url.clone();
+            if let Some(marker) = &marker {
+                page_url.query_pairs_mut().append_pair("marker", marker);
+            }
+
+            let body = self.send(Method::GET, page_url, None, None).await?;
+            let page: EnumerationResults = quick_xml::de::from_str(&body.text().await?)?;
+            marker = page.next_marker.clone().filter(|m| !m.is_empty());
+            pages.push(page);
+            if marker.is_none() {
+                break;
+            }
+        }
+
+        Ok(pages)
+    }
+}
+
+impl StorageBackend for AzureBlobStorage {
+    async fn list_containers(&self) -> anyhow::Result<Vec<String>> {
+        let mut url = self.url(None, None)?;
+        url.query_pairs_mut().append_pair("comp", "list");
+
+        let pages = self.list_all(url).await?;
+        let containers = pages
+            .into_iter()
+            .flat_map(|p| p.containers.map(|c| c.items).unwrap_or_default())
+            .map(|c| c.name)
+            .collect();
+
+        Ok(containers)
+    }
+
+    async fn head_container(&self, container: &str) -> anyhow::Result<()> {
+        let mut url = self.url(Some(container), None)?;
+        url.query_pairs_mut().append_pair("restype", "container");
+
+        self.send(Method::HEAD, url, None, None).await?;
+        Ok(())
+    }
+
+    async fn list_objects(&self, container: &str, query: &ListQuery) -> anyhow::Result<ObjectPage> {
+        let mut url = self.url(Some(container), None)?;
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs
+                .append_pair("restype", "container")
+                .append_pair("comp", "list")
+                .append_pair("maxresults", &query.limit().to_string());
+            if let Some(prefix) = query.prefix() {
+                pairs.append_pair("prefix", prefix);
+            }
+            if let Some(delimiter) = query.delimiter() {
+                pairs.append_pair("delimiter", delimiter);
+            }
+            if let Some(marker) = &query.cursor {
+                pairs.append_pair("marker", marker);
+            }
+        }
+
+        let body = self.send(Method::GET, url, None, None).await?;
+        let page: EnumerationResults = quick_xml::de::from_str(&body.text().await?)?;
+        let (objects, prefixes) = match page.blobs {
+            Some(blobs) => (blobs.items, blobs.prefixes),
+            None => Default::default(),
+        };
+
+        Ok(ObjectPage {
+            objects: objects.into_iter().map(ObjectInfo::from).collect(),
+            prefixes: prefixes.into_iter().map(|p| p.name).collect(),
+            next_cursor: page.next_marker.filter(|m| !m.is_empty()),
+        })
+    }
+
+    async fn head_object(&self, container: &str, key: &str) -> anyhow::Result<ObjectInfo> {
+        let url = self.url(Some(container), Some(key))?;
+        let response = self.send(Method::HEAD, url, None, None).await?;
+        let headers = response.headers();
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: header(CONTENT_LENGTH)
+                .and_then(|l| l.parse().ok())
+                .unwrap_or_default(),
+            last_modified: header(LAST_MODIFIED).and_then(http_date_to_rfc3339),
+            etag: header(ETAG),
+            content_type: header(CONTENT_TYPE),
+        })
+    }
+
+    async fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<ByteStream> {
+        let range =
+            utils::calculate_range_header(offset, len).ok_or(anyhow::anyhow!("range overflow"))?;
+        let url = self.url(Some(container), Some(key))?;
+
+        let response = self.send(Method::GET, url, Some(range), None).await?;
+        let stream = response.bytes_stream().map_err(anyhow::Error::from);
+
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Build the Shared Key string to sign of a request
+fn string_to_sign(method: &Method, url: &Url, headers: &HeaderMap, account: &str) -> String {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    // An empty Content-Length is signed for bodiless requests
+    let content_length = match header("content-length") {
+        "0" => "",
+        l => l,
+    };
+
+    let mut ms_headers = headers
+        .iter()
+        .filter(|(k, _)| k.as_str().starts_with("x-ms-"))
+        .map(|(k, v)| format!("{}:{}\n", k.as_str(), v.to_str().unwrap_or_default().trim()))
+        .collect::<Vec<_>>();
+    ms_headers.sort();
+
+    let mut query = url
+        .query_pairs()
+        .map(|(k, v)| (k.to_lowercase(), v.into_owned()))
+        .collect::<Vec<_>>();
+    query.sort();
+    let resource = query
+        .into_iter()
+        .fold(format!("/{account}{}", url.path()), |resource, (k, v)| {
+            format!("{resource}\n{k}:{v}")
+        });
+
+    format!(
+        "{method}\n\n\n{content_length}\n\n{}\n\n\n\n\n\n\n{}{resource}",
+        header("content-type"),
+        ms_headers.concat(),
+    )
+}
+
+/// Sign a string with the account's shared key
+fn sign(key: &[u8], string_to_sign: &str) -> anyhow::Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(string_to_sign.as_bytes());
+
+    Ok(general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+/// Convert an HTTP date to RFC 3339
+fn http_date_to_rfc3339(date: String) -> Option<String> {
+    let date = DateTime::from_str(&date, Format::HttpDate).ok()?;
+    date.fmt(Format::DateTime).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::collect;
+
+    /// The well-known Azurite development account
+    const AZURITE_ACCOUNT: &str = "devstoreaccount1";
+    /// The well-known Azurite development key
+    const AZURITE_KEY: &str =
+        "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+    #[test]
+    fn test_string_to_sign() -> anyhow::Result<()> {
+        let url = Url::parse(
+            "http://127.0.0.1:10000/devstoreaccount1/media?restype=container&comp=list",
+        )?;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ms-version", API_VERSION.parse()?);
+        headers.insert("x-ms-date", "Mon, 01 Jan 2024 00:00:00 GMT".parse()?);
+
+        let string_to_sign = string_to_sign(&Method::GET, &url, &headers, AZURITE_ACCOUNT);
+        let expected = "GET\n\n\n\n\n\n\n\n\n\n\n\n\
+            x-ms-date:Mon, 01 Jan 2024 00:00:00 GMT\n\
+            x-ms-version:2021-08-06\n\
+            /devstoreaccount1/devstoreaccount1/media\ncomp:list\nrestype:container";
+        assert_eq!(string_to_sign, expected);
+
+        // HMAC-SHA256 of the string above with the Azurite key
+        let key = general_purpose::STANDARD.decode(AZURITE_KEY)?;
+        assert_eq!(
+            sign(&key, &string_to_sign)?,
+            "zkKhdCjRaKiKJrNTl/tUExfA6rh/0sCChNtlAarvzS0="
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_blobs_response() -> anyhow::Result<()> {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <EnumerationResults ServiceEndpoint="http://127.0.0.1:10000/devstoreaccount1" ContainerName="media">
+              <Blobs>
+                <BlobPrefix>
+                  <Name>music/</Name>
+                </BlobPrefix>
+                <Blob>
+                  <Name>videos/movie.mp4</Name>
+                  <Properties>
+                    <Last-Modified>Mon, 01 Jan 2024 00:00:00 GMT</Last-Modified>
+                    <Etag>0x8DC0A</Etag>
+                    <Content-Length>1337</Content-Length>
+                    <Content-Type>video/mp4</Content-Type>
+                  </Properties>
+                </Blob>
+                <BlobPrefix>
+                  <Name>videos/extras/</Name>
+                </BlobPrefix>
+              </Blobs>
+              <NextMarker />
+            </EnumerationResults>"#;
+        let page: EnumerationResults = quick_xml::de::from_str(xml)?;
+        let blobs = page.blobs.unwrap();
+        let prefixes = blobs
+            .prefixes
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        let info = ObjectInfo::from(blobs.items.into_iter().next().unwrap());
+
+        assert_eq!(info.key, "videos/movie.mp4");
+        assert_eq!(info.size, 1337);
+        assert_eq!(info.last_modified.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(prefixes, vec!["music/", "videos/extras/"]);
+        assert_eq!(page.next_marker.filter(|m| !m.is_empty()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_url() -> anyhow::Result<()> {
+        let storage = AzureBlobStorage::new("account", None, Some("?sv=1&sig=x"), None)?;
+        let url = storage.url(Some("media"), Some("videos/my movie.mp4"))?;
+
+        assert_eq!(
+            url.as_str(),
+            "https://account.blob.core.windows.net/media/videos/my%20movie.mp4"
+        );
+        assert_eq!(storage.sas_token.as_deref(), Some("sv=1&sig=x"));
+
+        // Account names cannot point the default host elsewhere
+        assert!(AzureBlobStorage::new("evil.com/#", None, None, None).is_err());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_azurite_roundtrip() -> anyhow::Result<()> {
+        dotenv::dotenv().ok();
+        const CONTAINER: &str = "xbyte-test";
+        const BLOB: &str = "videos/hello.txt";
+
+        let Ok(endpoint) = std::env::var("AZURITE_ENDPOINT") else {
+            eprintln!("No Azurite endpoint configured, skipping test");
+            return Ok(());
+        };
+        let storage =
+            AzureBlobStorage::new(AZURITE_ACCOUNT, Some(AZURITE_KEY), None, Some(&endpoint))?;
+
+        // Seed the container, which may be left over from a previous run
+        let mut url = storage.url(Some(CONTAINER), None)?;
+        url.query_pairs_mut().append_pair("restype", "container");
+        let _ = storage.send(Method::PUT, url, None, None).await;
+        let url = storage.url(Some(CONTAINER), Some(BLOB))?;
+        let body = ("text/plain", b"hello xbyte".to_vec());
+        storage.send(Method::PUT, url, None, Some(body)).await?;
+
+        // Verify the data
+        storage.head_container(CONTAINER).await?;
+        assert!(
+            storage
+                .list_containers()
+                .await?
+                .iter()
+                .any(|c| c == CONTAINER)
+        );
+        let query = ListQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = storage.list_objects(CONTAINER, &query).await?;
+        assert_eq!(page.objects.len(), 1);
+        let query = ListQuery {
+            prefix: Some(String::from(BLOB)),
+            ..query
+        };
+        let page = storage.list_objects(CONTAINER, &query).await?;
+        assert_eq!(page.objects[0].key, BLOB);
+        let info = storage.head_object(CONTAINER, BLOB).await?;
+        assert_eq!(info.size, 11);
+        assert_eq!(info.content_type.as_deref(), Some("text/plain"));
+        let data = collect(storage.read_range(CONTAINER, BLOB, 6, 5).await?).await?;
+        assert_eq!(data.as_ref(), b"xbyte");
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+use crate::metrics::METRICS;
+use crate::propagation::TraceInterceptor;
+use crate::{AzureBlobStorage, Backend, GcsStorage, LocalStorage, ServerConfig, Storage, XByteS3};
+use aws_sdk_sts::Client as StsClient;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use url::{Origin, Url};
+
+/// The session name used when assuming roles
+const SESSION_NAME: &str = "xbyte-s3";
+/// Refresh credentials in the background once they are this close to expiring
+const REFRESH_WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Stop reusing credentials once they are this close to expiring
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// A cached storage backend
+#[derive(Debug, Clone)]
+struct CachedBackend {
+    /// The storage backend
+    backend: Backend,
+    /// When the credentials expire, if they ever do
+    expiration: Option<SystemTime>,
+}
+
+/// A cache of storage backends keyed by storage (role ARN and region for AWS)
+#[derive(Debug, Clone)]
+pub struct BackendCache {
+    /// The STS client used to assume roles
+    sts: StsClient,
+    /// The cached backends
+    entries: Arc<RwLock<HashMap<Storage<String>, CachedBackend>>>,
+    /// The storages currently being refreshed in the background
+    refreshing: Arc<RwLock<HashSet<Storage<String>>>>,
+    /// Bumped on every eviction of a storage, so its refreshes started before are not cached
+    generations: Arc<RwLock<HashMap<Storage<String>, u64>>>,
+    /// The directory local storages must live in, local storage is disabled without it
+    local_root: Option<Arc<PathBuf>>,
+    /// The origins storages may use as custom endpoints, none are allowed by default
+    endpoints: Arc<Vec<Origin>>,
+}
+
+impl BackendCache {
+    /// Create a new cache
+    pub fn new(sts: StsClient) -> Self {
+        Self {
+            sts,
+            entries: Default::default(),
+            refreshing: Default::default(),
+            generations: Default::default(),
+            local_root: None,
+            endpoints: Default::default(),
+        }
+    }
+
+    /// Create the cache of a server, assuming roles with its AWS settings
+    pub async fn from_config(config: &ServerConfig) -> Self {
+        let aws_config = crate::server::aws_config(&config.aws).await;
+        let sts = aws_sdk_sts::config::Builder::from(&aws_config)
+            .interceptor(TraceInterceptor)
+            .build();
+        let cache = Self::new(StsClient::from_conf(sts)).with_endpoints(&config.storage_endpoints);
+        match config.local_root.clone() {
+            Some(root) => cache.with_local_root(root),
+            None => cache,
+        }
+    }
+
+    /// Check that the STS credentials of the server are valid
+    pub async fn caller_identity(&self) -> anyhow::Result<String> {
+        let identity = self.sts.get_caller_identity().send().await?;
+        identity
+            .arn
+            .ok_or(anyhow::anyhow!("no caller identity returned"))
+    }
+
+    /// Allow local storages below the given directory
+    pub fn with_local_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.local_root = Some(Arc::new(root.into()));
+        self
+    }
+
+    /// Allow storages to use custom endpoints on the given origins, ignoring invalid ones
+    pub fn with_endpoints<S: AsRef<str>>(mut self, endpoints: &[S]) -> Self {
+        let origins = endpoints
+            .iter()
+            .filter_map(|e| Url::parse(e.as_ref()).ok())
+            .map(|url| url.origin())
+            .filter(Origin::is_tuple);
+        self.endpoints = Arc::new(origins.collect());
+        self
+    }
+
+    /// Check that a custom endpoint is on an allowed origin, so clients cannot reach
+    /// arbitrary hosts (e.g. cloud metadata) through the server
+    fn endpoint(&self, endpoint: Option<&str>) -> anyhow::Result<()> {
+        let Some(endpoint) = endpoint else {
+            return Ok(());
+        };
+
+        let origin = Url::parse(endpoint)?.origin();
+        match self.endpoints.contains(&origin) {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!(
+                "storage endpoint {endpoint} is not allowed"
+            )),
+        }
+    }
+
+    /// Resolve the storage to a backend, creating one only when no usable backend is cached
+    pub async fn backend(&self, storage: &Storage<String>) -> anyhow::Result<Backend> {
+        if let Storage::Local { root } = storage {
+            return self.local(root).map(Backend::Local);
+        }
+
+        let now = SystemTime::now();
+        let cached = self.entries.read().unwrap().get(storage).cloned();
+        match cached {
+            Some(CachedBackend {
+                backend,
+                expiration: None,
+            }) => return Ok(backend),
+            Some(CachedBackend {
+                backend,
+                expiration: Some(expiration),
+            }) => {
+                if now + REFRESH_WINDOW < expiration {
+                    return Ok(backend);
+                }
+                if now + EXPIRY_MARGIN < expiration {
+                    self.refresh_in_background(storage.clone());
+                    return Ok(backend);
+                }
+            }
+            None => (),
+        }
+
+        self.refresh(storage.clone()).await
+    }
+
+    /// Create a local storage, making sure it lives below the allowed root
+    fn local(&self, root: &str) -> anyhow::Result<LocalStorage> {
+        let Some(allowed) = &self.local_root else {
+            return Err(anyhow::anyhow!("local storage is disabled"));
+        };
+
+        let root = Path::new(root).canonicalize()?;
+        if !root.starts_with(allowed.canonicalize()?) {
+            return Err(anyhow::anyhow!("local storage outside of the allowed root"));
+        }
+
+        Ok(LocalStorage::new(root))
+    }
+
+    /// Evict the cached backend for the storage, e.g. once a client's storage changed
+    pub fn evict(&self, storage: &Storage<String>) {
+        let mut entries = self.entries.write().unwrap();
+        entries.remove(storage);
+        let mut generations = self.generations.write().unwrap();
+        *generations.entry(storage.clone()).or_default() += 1;
+    }
+
+    /// The number of times the storage was evicted
+    fn generation(&self, storage: &Storage<String>) -> u64 {
+        let generations = self.generations.read().unwrap();
+        generations.get(storage).copied().unwrap_or_default()
+    }
+
+    /// Create the backend for the storage and cache it
+    async fn refresh(&self, storage: Storage<String>) -> anyhow::Result<Backend> {
+        let generation = self.generation(&storage);
+        let entry = self.create(&storage).await?;
+        let backend = entry.backend.clone();
+        self.store(storage, entry, generation);
+
+        Ok(backend)
+    }
+
+    /// Cache a backend created at the given generation, unless the storage was evicted since,
+    /// e.g. a refresh still assuming a role that was revoked meanwhile
+    fn store(&self, storage: Storage<String>, entry: CachedBackend, generation: u64) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        if self.generation(&storage) != generation {
+            return false;
+        }
+
+        entries.insert(storage, entry);
+        true
+    }
+
+    /// Create the backend for the storage
+    async fn create(&self, storage: &Storage<String>) -> anyhow::Result<CachedBackend> {
+        match storage {
+            Storage::S3Compatible { endpoint, .. } => self.endpoint(Some(endpoint))?,
+            Storage::Gcs { endpoint, .. } | Storage::AzureBlob { endpoint, .. } => {
+                self.endpoint(endpoint.as_deref())?
+            }
+            _ => (),
+        }
+
+        let entry = match storage {
+            Storage::S3 { role_arn, region } => {
+                let timer = METRICS
+                    .storage_duration
+                    .with_label_values(&["sts", "assume_role"])
+                    .start_timer();
+                let (client, expiration) =
+                    XByteS3::assume_role(&self.sts, role_arn, SESSION_NAME, region.clone()).await?;
+                timer.observe_duration();
+                CachedBackend {
+                    backend: Backend::S3(client),
+                    expiration: Some(expiration),
+                }
+            }
+            Storage::S3Compatible {
+                endpoint,
+                region,
+                access_key_id,
+                secret_access_key,
+                path_style,
+            } => CachedBackend {
+                backend: Backend::S3(XByteS3::new_static(
+                    endpoint,
+                    region.clone(),
+                    access_key_id,
+                    secret_access_key,
+                    *path_style,
+                )),
+                expiration: None,
+            },
+            Storage::Gcs {
+                project_id,
+                service_account_key,
+                endpoint,
+            } => CachedBackend {
+                backend: Backend::Gcs(GcsStorage::new(
+                    project_id,
+                    service_account_key.as_deref(),
+                    endpoint.as_deref(),
+                )?),
+                expiration: None,
+            },
+            Storage::AzureBlob {
+                account,
+                access_key,
+                sas_token,
+                endpoint,
+            } => CachedBackend {
+                backend: Backend::Azure(AzureBlobStorage::new(
+                    account,
+                    access_key.as_deref(),
+                    sas_token.as_deref(),
+                    endpoint.as_deref(),
+                )?),
+                expiration: None,
+            },
+            Storage::Local { .. } => return Err(anyhow::anyhow!("local storages are not cached")),
+        };
+
+        Ok(entry)
+    }
+
+    /// Refresh the storage in the background, unless a refresh is already running
+    fn refresh_in_background(&self, storage: Storage<String>) {
+        if !self.refreshing.write().unwrap().insert(storage.clone()) {
+            return;
+        }
+
+        let cache = self.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(error) = cache.refresh(storage.clone()).await {
+                tracing::warn!(?error, "Failed to refresh storage credentials");
+            }
+            cache.refreshing.write().unwrap().remove(&storage);
+        });
+    }
+
+    /// Insert an S3 client directly, bypassing STS
+    #[cfg(test)]
+    fn insert(&self, storage: Storage<String>, client: XByteS3, expiration: SystemTime) {
+        let entry = CachedBackend {
+            backend: Backend::S3(client),
+            expiration: Some(expiration),
+        };
+        self.entries.write().unwrap().insert(storage, entry);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+
+    const REGION: &str = "us-east-1";
+
+    /// An AWS storage assuming a test role
+    fn test_storage() -> Storage<String> {
+        Storage::S3 {
+            role_arn: String::from("arn:aws:iam::123456789012:role/xByte"),
+            region: String::from(REGION),
+        }
+    }
+
+    /// An STS client pointing to an unreachable endpoint
+    pub(crate) fn offline_sts() -> StsClient {
+        let config = aws_sdk_sts::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(REGION))
+            .endpoint_url("http://127.0.0.1:1")
+            .credentials_provider(Credentials::for_tests())
+            .retry_config(aws_sdk_sts::config::retry::RetryConfig::disabled())
+            .build();
+
+        StsClient::from_conf(config)
+    }
+
+    /// An S3 client that never leaves the machine
+    fn offline_s3() -> XByteS3 {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(REGION))
+            .credentials_provider(Credentials::for_tests())
+            .build();
+
+        XByteS3::from(aws_sdk_s3::Client::from_conf(config))
+    }
+
+    #[actix_web::test]
+    async fn test_cache_hit() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let expiration = SystemTime::now() + Duration::from_secs(3600);
+        cache.insert(test_storage(), offline_s3(), expiration);
+
+        // Served from the cache without reaching STS
+        cache.backend(&test_storage()).await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_expired() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let expiration = SystemTime::now() + Duration::from_secs(10);
+        cache.insert(test_storage(), offline_s3(), expiration);
+
+        // Too close to expiring, so STS is called (and fails offline)
+        assert!(cache.backend(&test_storage()).await.is_err());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_evict() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let expiration = SystemTime::now() + Duration::from_secs(3600);
+        cache.insert(test_storage(), offline_s3(), expiration);
+
+        cache.evict(&test_storage());
+        assert!(cache.backend(&test_storage()).await.is_err());
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_evict_during_refresh() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let entry = CachedBackend {
+            backend: Backend::S3(offline_s3()),
+            expiration: Some(SystemTime::now() + Duration::from_secs(3600)),
+        };
+
+        // A refresh finishing after an eviction is not cached
+        let generation = cache.generation(&test_storage());
+        cache.evict(&test_storage());
+        assert!(!cache.store(test_storage(), entry.clone(), generation));
+        assert!(cache.backend(&test_storage()).await.is_err());
+
+        // Later refreshes are
+        let generation = cache.generation(&test_storage());
+        assert!(cache.store(test_storage(), entry.clone(), generation));
+        cache.backend(&test_storage()).await?;
+
+        // Evicting another storage leaves the refreshes of this one alone
+        let other = Storage::S3 {
+            role_arn: String::from("arn:aws:iam::123456789012:role/other"),
+            region: String::from(REGION),
+        };
+        let generation = cache.generation(&test_storage());
+        cache.evict(&other);
+        assert!(cache.store(test_storage(), entry, generation));
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_s3_compatible() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let storage = Storage::S3Compatible {
+            endpoint: String::from("http://127.0.0.1:9000"),
+            region: String::from(REGION),
+            access_key_id: String::from("minio"),
+            secret_access_key: String::from("minio123"),
+            path_style: true,
+        };
+
+        // The endpoint must be allowed, static credentials never reach STS
+        assert!(cache.backend(&storage).await.is_err());
+        let cache = cache.with_endpoints(&["http://127.0.0.1:9000"]);
+        cache.backend(&storage).await?;
+        cache.backend(&storage).await?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_gcs_and_azure() -> anyhow::Result<()> {
+        let cache = BackendCache::new(offline_sts());
+        let gcs = Storage::Gcs {
+            project_id: String::from("xbyte"),
+            service_account_key: None,
+            endpoint: Some(String::from("http://127.0.0.1:4443")),
+        };
+        let azure = Storage::AzureBlob {
+            account: String::from("devstoreaccount1"),
+            access_key: None,
+            sas_token: Some(String::from("sv=2021-08-06&sig=test")),
+            endpoint: None,
+        };
+
+        // Custom endpoints must be allowed
+        assert!(cache.backend(&gcs).await.is_err());
+        let cache = cache.with_endpoints(&["http://127.0.0.1:4443"]);
+        assert!(matches!(cache.backend(&gcs).await?, Backend::Gcs(_)));
+        assert!(matches!(cache.backend(&azure).await?, Backend::Azure(_)));
+
+        // Other hosts, ports or schemes are not
+        for endpoint in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://127.0.0.1:4444",
+            "https://127.0.0.1:4443",
+        ] {
+            let azure = Storage::AzureBlob {
+                account: String::from("devstoreaccount1"),
+                access_key: None,
+                sas_token: None,
+                endpoint: Some(String::from(endpoint)),
+            };
+            assert!(cache.backend(&azure).await.is_err(), "{endpoint}");
+        }
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_cache_local_root() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("xbyte-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("creator"))?;
+        let inside = Storage::Local {
+            root: root.join("creator").to_string_lossy().into_owned(),
+        };
+        let outside = Storage::Local {
+            root: std::env::temp_dir().to_string_lossy().into_owned(),
+        };
+
+        // Disabled by default
+        let cache = BackendCache::new(offline_sts());
+        assert!(cache.backend(&inside).await.is_err());
+
+        // Only storages below the allowed root
+        let cache = cache.with_local_root(&root);
+        assert!(cache.backend(&inside).await.is_ok());
+        assert!(cache.backend(&outside).await.is_err());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+use crate::storage::{ByteStream, ListQuery, ObjectInfo, ObjectPage, StorageBackend};
+use crate::utils;
+use futures_util::TryStreamExt;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// The default Google Cloud Storage endpoint
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+/// The OAuth endpoint service account keys exchange their JWT at
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+/// The OAuth scope requested for service accounts
+const READ_ONLY_SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_only";
+/// Refresh access tokens once they are this close to expiring
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// A service account key as downloaded from the Google Cloud console
+#[derive(Debug, Clone, Deserialize)]
+struct ServiceAccountKey {
+    /// The service account email
+    client_email: String,
+    /// The PEM encoded RSA private key
+    private_key: String,
+    /// The OAuth token endpoint
+    token_uri: String,
+}
+
+/// The claims of a service account JWT
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+/// The OAuth token response
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// A page of a GCS list response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListResponse<T> {
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+    #[serde(default)]
+    prefixes: Vec<String>,
+    next_page_token: Option<String>,
+}
+
+/// A GCS bucket resource
+#[derive(Debug, Deserialize)]
+struct BucketResource {
+    name: String,
+}
+
+/// A GCS object resource
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectResource {
+    name: String,
+    size: Option<String>,
+    updated: Option<String>,
+    etag: Option<String>,
+    content_type: Option<String>,
+}
+
+impl From<ObjectResource> for ObjectInfo {
+    fn from(object: ObjectResource) -> Self {
+        Self {
+            size: object.size.and_then(|s| s.parse().ok()).unwrap_or_default(),
+            key: object.name,
+            last_modified: object.updated,
+            etag: object.etag,
+            content_type: object.content_type,
+        }
+    }
+}
+
+/// A storage backend for Google Cloud Storage through its JSON API
+#[derive(Debug, Clone)]
+pub struct GcsStorage {
+    /// The HTTP client
+    http: reqwest::Client,
+    /// The API endpoint
+    endpoint: Url,
+    /// The project the buckets belong to
+    project_id: String,
+    /// The service account key, anonymous access without it
+    key: Option<Arc<ServiceAccountKey>>,
+    /// The cached access token and its expiration
+    token: Arc<RwLock<Option<(String, SystemTime)>>>,
+}
+
+impl GcsStorage {
+    /// Create a new GCS storage, using the service account key (JSON) if given
+    pub fn new(
+        project_id: &str,
+        service_account_key: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let custom = endpoint.is_some();
+        let endpoint = Url::parse(endpoint.unwrap_or(DEFAULT_ENDPOINT))?;
+        let key = service_account_key
+            .map(serde_json::from_str::<ServiceAccountKey>)
+            .transpose()?
+            .map(Arc::new);
+
+        // The signed JWT is only sent to Google, or to the emulator serving the API
+        if let Some(key) = &key {
+            let token_uri = Url::parse(&key.token_uri)?;
+            let emulated = custom && token_uri.origin() == endpoint.origin();
+            if key.token_uri != TOKEN_URI && !emulated {
+                return Err(anyhow::anyhow!(
+                    "token_uri {} is not allowed",
+                    key.token_uri
+                ));
+            }
+        }
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            endpoint,
+            project_id: project_id.to_string(),
+            key,
+            token: Default::default(),
+        })
+    }
+
+    /// Build a URL below the endpoint from path segments
+    fn url(&self, segments: &[&str]) -> anyhow::Result<Url> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("invalid GCS endpoint"))?
+            .pop_if_empty()
+            .extend(segments);
+
+        Ok(url)
+    }
+
+    /// Get an access token, exchanging a fresh service account JWT when needed
+    async fn access_token(&self) -> anyhow::Result<Option<String>> {
+        let Some(key) = &self.key else {
+            return Ok(None);
+        };
+
+        let cached = self.token.read().unwrap().clone();
+        if let Some((token, expiration)) = cached
+            && SystemTime::now() + TOKEN_MARGIN < expiration
+        {
+            return Ok(Some(token));
+        }
+
+        let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = Claims {
+            iss: &key.client_email,
+            scope: READ_ONLY_SCOPE,
+            aud: &key.token_uri,
+            iat,
+            exp: iat + 3600,
+        };
+        let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+        let signer = jsonwebtoken::EncodingKey::from_rsa_pem(key.private_key.as_bytes())?;
+        let assertion = jsonwebtoken::encode(&header, &claims, &signer)?;
+
+        let response: TokenResponse = self
+            .http
+            .post(&key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let expiration = SystemTime::now() + Duration::from_secs(response.expires_in);
+        *self.token.write().unwrap() = Some((response.access_token.clone(), expiration));
+
+        Ok(Some(response.access_token))
+    }
+
+    /// Send an authorized request, failing on error statuses
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let request = match self.access_token().await? {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+
+        let request = crate::trace_headers()
+            .into_iter()
+            .fold(request, |request, (name, value)| {
+                request.header(name, value)
+            });
+        let response = request.send().await?;
+        match response.status() {
+            StatusCode::RANGE_NOT_SATISFIABLE => Err(anyhow::anyhow!("range not satisfiable")),
+            _ => Ok(response.error_for_status()?),
+        }
+    }
+
+    /// Fetch every page of a list endpoint
+    async fn list_all<T: serde::de::DeserializeOwned>(
+        &self,
+        url: Url,
+        query: &[(&str, &str)],
+    ) -> anyhow::Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut page_token = None::<String>;
+        loop {
+            let mut request = self.http.get(url.clone()).query(query);
+            if let Some(token) = &page_token {
+                request = request.query(&[("pageToken", token)]);
+            }
+
+            let page: ListResponse<T> = self.send(request).await?.json().await?;
+            items.extend(page.items);
+            page_token = page.next_page_token.filter(|t| !t.is_empty());
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+impl StorageBackend for GcsStorage {
+    async fn list_containers(&self) -> anyhow::Result<Vec<String>> {
+        let url = self.url(&["storage", "v1", "b"])?;
+        let query = [("project", self.project_id.as_str())];
+        let buckets: Vec<BucketResource> = self.list_all(url, &query).await?;
+
+        Ok(buckets.into_iter().map(|b| b.name).collect())
+    }
+
+    async fn head_container(&self, container: &str) -> anyhow::Result<()> {
+        let url = self.url(&["storage", "v1", "b", container])?;
+        self.send(self.http.get(url)).await?;
+        Ok(())
+    }
+
+    async fn list_objects(&self, container: &str, query: &ListQuery) -> anyhow::Result<ObjectPage> {
+        let url = self.url(&["storage", "v1", "b", container, "o"])?;
+        let mut params = vec![("maxResults", query.limit().to_string())];
+        params.extend(query.prefix().map(|p| ("prefix", p.to_string())));
+        params.extend(query.delimiter().map(|d| ("delimiter", d.to_string())));
+        params.extend(query.cursor.clone().map(|c| ("pageToken", c)));
+
+        let request = self.http.get(url).query(&params);
+        let page: ListResponse<ObjectResource> = self.send(request).await?.json().await?;
+
+        Ok(ObjectPage {
+            objects: page.items.into_iter().map(ObjectInfo::from).collect(),
+            prefixes: page.prefixes,
+            next_cursor: page.next_page_token.filter(|t| !t.is_empty()),
+        })
+    }
+
+    async fn head_object(&self, container: &str, key: &str) -> anyhow::Result<ObjectInfo> {
+        let url = self.url(&["storage", "v1", "b", container, "o", key])?;
+        let object: ObjectResource = self.send(self.http.get(url)).await?.json().await?;
+
+        Ok(object.into())
+    }
+
+    async fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<ByteStream> {
+        let range =
+            utils::calculate_range_header(offset, len).ok_or(anyhow::anyhow!("range overflow"))?;
+        let url = self.url(&["storage", "v1", "b", container, "o", key])?;
+        let request = self
+            .http
+            .get(url)
+            .query(&[("alt", "media")])
+            .header(reqwest::header::RANGE, range);
+
+        let response = self.send(request).await?;
+        let stream = response.bytes_stream().map_err(anyhow::Error::from);
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::collect;
+
+    #[test]
+    fn test_object_resource() -> anyhow::Result<()> {
+        let json = r#"{"name":"videos/movie.mp4","size":"1337","updated":"2024-01-01T00:00:00.000Z","etag":"CKih16GjycICEAE=","contentType":"video/mp4"}"#;
+        let info = ObjectInfo::from(serde_json::from_str::<ObjectResource>(json)?);
+
+        assert_eq!(info.key, "videos/movie.mp4");
+        assert_eq!(info.size, 1337);
+        assert_eq!(info.content_type.as_deref(), Some("video/mp4"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_url() -> anyhow::Result<()> {
+        let storage = GcsStorage::new("project", None, Some("http://localhost:4443/"))?;
+        let url = storage.url(&["storage", "v1", "b", "media", "o", "videos/movie.mp4"])?;
+
+        assert_eq!(
+            url.as_str(),
+            "http://localhost:4443/storage/v1/b/media/o/videos%2Fmovie.mp4"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_service_account_key() {
+        assert!(GcsStorage::new("project", Some("{}"), None).is_err());
+    }
+
+    #[test]
+    fn test_token_uri() {
+        let key = |token_uri: &str| {
+            serde_json::json!({
+                "client_email": "xbyte@project.iam.gserviceaccount.com",
+                "private_key": "",
+                "token_uri": token_uri,
+            })
+            .to_string()
+        };
+        let emulator = Some("http://localhost:4443");
+
+        assert!(GcsStorage::new("project", Some(&key(TOKEN_URI)), None).is_ok());
+        let local = key("http://localhost:4443/token");
+        assert!(GcsStorage::new("project", Some(&local), emulator).is_ok());
+
+        // The JWT is never posted anywhere else
+        let metadata = key("http://169.254.169.254/token");
+        assert!(GcsStorage::new("project", Some(&metadata), None).is_err());
+        assert!(GcsStorage::new("project", Some(&metadata), emulator).is_err());
+        assert!(GcsStorage::new("project", Some(&local), None).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_gcs_emulator_roundtrip() -> anyhow::Result<()> {
+        dotenv::dotenv().ok();
+        const BUCKET: &str = "xbyte-test";
+        const OBJECT: &str = "videos/hello.txt";
+
+        let Ok(endpoint) = std::env::var("GCS_EMULATOR_ENDPOINT") else {
+            eprintln!("No GCS emulator configured, skipping test");
+            return Ok(());
+        };
+        let storage = GcsStorage::new("xbyte", None, Some(&endpoint))?;
+
+        // Seed the bucket, which may be left over from a previous run
+        let url = storage.url(&["storage", "v1", "b"])?;
+        let bucket = serde_json::json!({ "name": BUCKET });
+        let request = storage.http.post(url).query(&[("project", "xbyte")]);
+        let _ = request.json(&bucket).send().await;
+        let url = storage.url(&["upload", "storage", "v1", "b", BUCKET, "o"])?;
+        let request = storage.http.post(url).body("hello xbyte");
+        let request = request.query(&[("uploadType", "media"), ("name", OBJECT)]);
+        storage.send(request).await?;
+
+        // Verify the data
+        storage.head_container(BUCKET).await?;
+        assert!(storage.list_containers().await?.iter().any(|b| b == BUCKET));
+        let query = ListQuery {
+            delimiter: Some(String::from("/")),
+            ..Default::default()
+        };
+        let page = storage.list_objects(BUCKET, &query).await?;
+        assert!(page.prefixes.iter().any(|p| p == "videos/"));
+        let query = ListQuery {
+            prefix: Some(String::from("videos/")),
+            ..query
+        };
+        let page = storage.list_objects(BUCKET, &query).await?;
+        assert!(page.objects.iter().any(|o| o.key == OBJECT));
+        let info = storage.head_object(BUCKET, OBJECT).await?;
+        assert_eq!(info.size, 11);
+        let data = collect(storage.read_range(BUCKET, OBJECT, 6, 5).await?).await?;
+        assert_eq!(data.as_ref(), b"xbyte");
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+use crate::storage::{self, ByteStream, ListQuery, ObjectInfo, ObjectPage, StorageBackend};
+use actix_web::web::{self, Bytes};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat as Format};
+use futures_util::stream;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The size of the chunks a range is streamed in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A storage backend serving files from a local directory, one subdirectory per bucket
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    /// The root directory
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create a new local storage rooted at the given directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a bucket and optional key below the root, rejecting paths escaping it
+    ///
+    /// Symlinks are followed, so an existing path must still lead below the root once resolved.
+    fn resolve(&self, bucket: &str, key: Option<&str>) -> anyhow::Result<PathBuf> {
+        let mut path = self.root.clone();
+        for part in std::iter::once(bucket).chain(key) {
+            if part.is_empty() {
+                return Err(anyhow::anyhow!("empty path segment"));
+            }
+            for component in Path::new(part).components() {
+                match component {
+                    Component::Normal(c) => path.push(c),
+                    _ => return Err(anyhow::anyhow!("invalid path segment: {part}")),
+                }
+            }
+        }
+
+        match path.canonicalize() {
+            Ok(real) if real.starts_with(self.root.canonicalize()?) => Ok(real),
+            Ok(_) => Err(anyhow::anyhow!("path escapes the root: {}", path.display())),
+            // Missing paths are reported by the callers
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(path),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    async fn list_containers(&self) -> anyhow::Result<Vec<String>> {
+        let root = self.root.clone();
+        web::block(move || {
+            let mut buckets = Vec::new();
+            for entry in fs::read_dir(root)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    buckets.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+
+            buckets.sort();
+            Ok(buckets)
+        })
+        .await?
+    }
+
+    async fn head_container(&self, container: &str) -> anyhow::Result<()> {
+        let dir = self.resolve(container, None)?;
+        match dir.is_dir() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("bucket not found: {container}")),
+        }
+    }
+
+    async fn list_objects(&self, container: &str, query: &ListQuery) -> anyhow::Result<ObjectPage> {
+        let dir = self.resolve(container, None)?;
+        let root = self.root.canonicalize()?;
+        let query = query.clone();
+        web::block(move || {
+            let mut paths = HashMap::new();
+            let mut pending = vec![dir.clone()];
+            while let Some(current) = pending.pop() {
+                for entry in fs::read_dir(current)? {
+                    let entry = entry?;
+                    let path = entry.path();
+
+                    // Symlinks are listed when they lead below the root, never descended into
+                    if entry.file_type()?.is_symlink() {
+                        let inside = path.canonicalize().is_ok_and(|p| p.starts_with(&root));
+                        if !inside || path.is_dir() {
+                            continue;
+                        }
+                    }
+                    if path.is_dir() {
+                        pending.push(path);
+                        continue;
+                    }
+
+                    // Keys use `/` separators regardless of the platform
+                    let key =
+                        path.strip_prefix(&dir)?
+                            .components()
+                            .fold(String::new(), |mut key, c| {
+                                if !key.is_empty() {
+                                    key.push('/');
+                                }
+                                key.push_str(&c.as_os_str().to_string_lossy());
+                                key
+                            });
+                    paths.insert(key, path);
+                }
+            }
+
+            let (keys, prefixes, next_cursor) = paginate(paths.keys().cloned(), &query);
+            let objects = keys
+                .into_iter()
+                .map(|key| {
+                    let path = &paths[&key];
+                    object_info(key, path)
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            Ok(ObjectPage {
+                objects,
+                prefixes,
+                next_cursor,
+            })
+        })
+        .await?
+    }
+
+    async fn head_object(&self, container: &str, key: &str) -> anyhow::Result<ObjectInfo> {
+        let path = self.resolve(container, Some(key))?;
+        let key = key.to_string();
+        web::block(move || object_info(key, &path)).await?
+    }
+
+    async fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<ByteStream> {
+        let path = self.resolve(container, Some(key))?;
+        let file = web::block(move || {
+            let mut file = fs::File::open(path)?;
+            if offset >= file.metadata()?.len() {
+                return Err(anyhow::anyhow!("range not satisfiable"));
+            }
+
+            file.seek(SeekFrom::Start(offset))?;
+            Ok(file.take(len))
+        })
+        .await??;
+
+        // Read the range chunk by chunk off the async runtime
+        let stream = stream::try_unfold(file, |mut file| async move {
+            let (file, chunk) = web::block(move || {
+                let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+                (&mut file)
+                    .take(CHUNK_SIZE as u64)
+                    .read_to_end(&mut chunk)?;
+                anyhow::Ok((file, chunk))
+            })
+            .await??;
+
+            match chunk.is_empty() {
+                true => Ok(None),
+                false => Ok(Some((Bytes::from(chunk), file))),
+            }
+        });
+
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Select a page of keys in lexicographic order, grouping them into common prefixes by the
+/// delimiter, and return the object keys, the prefixes and the cursor of the next page
+fn paginate(
+    keys: impl Iterator<Item = String>,
+    query: &ListQuery,
+) -> (Vec<String>, Vec<String>, Option<String>) {
+    let prefix = query.prefix().unwrap_or_default();
+
+    // Entries are sorted and flagged whether they are a common prefix
+    let mut entries = BTreeMap::new();
+    for key in keys.filter(|k| k.starts_with(prefix)) {
+        let split = query.delimiter().and_then(|d| {
+            key[prefix.len()..]
+                .find(d)
+                .map(|i| prefix.len() + i + d.len())
+        });
+        match split {
+            Some(end) => entries.insert(key[..end].to_string(), true),
+            None => entries.insert(key, false),
+        };
+    }
+
+    let mut remaining = entries
+        .into_iter()
+        .filter(|(key, _)| query.cursor.as_ref().is_none_or(|c| key > c));
+    let page = remaining
+        .by_ref()
+        .take(query.limit() as usize)
+        .collect::<Vec<_>>();
+    let next_cursor = match remaining.next() {
+        Some(_) => page.last().map(|(key, _)| key.clone()),
+        None => None,
+    };
+
+    let (prefixes, keys): (Vec<_>, Vec<_>) = page.into_iter().partition(|(_, p)| *p);
+    let keys = keys.into_iter().map(|(key, _)| key).collect();
+    let prefixes = prefixes.into_iter().map(|(key, _)| key).collect();
+
+    (keys, prefixes, next_cursor)
+}
+
+/// Read the metadata of a file
+fn object_info(key: String, path: &Path) -> anyhow::Result<ObjectInfo> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(anyhow::anyhow!("object not found: {key}"));
+    }
+
+    let modified = metadata.modified().ok();
+    let last_modified = modified
+        .map(DateTime::from)
+        .and_then(|d| d.fmt(Format::DateTime).ok());
+    let etag = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|m| format!("\"{:x}-{:x}\"", metadata.len(), m.as_nanos()));
+    let content_type = storage::guess_content_type(&key);
+
+    Ok(ObjectInfo {
+        key,
+        size: metadata.len(),
+        last_modified,
+        etag,
+        content_type,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::storage::collect;
+
+    /// Create a temporary root with a `media` bucket holding `song.mp3` and `videos/movie.mp4`
+    pub(crate) fn temp_storage() -> anyhow::Result<LocalStorage> {
+        let root = std::env::temp_dir().join(format!("xbyte-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("media/videos"))?;
+        fs::write(root.join("media/song.mp3"), b"hello xbyte")?;
+        fs::write(root.join("media/videos/movie.mp4"), b"movie")?;
+
+        Ok(LocalStorage::new(root))
+    }
+
+    #[actix_web::test]
+    async fn test_list_containers() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+        let buckets = storage.list_containers().await?;
+
+        assert_eq!(buckets, vec!["media"]);
+        assert!(storage.head_container("media").await.is_ok());
+        assert!(storage.head_container("missing").await.is_err());
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_list_objects() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+        let page = storage.list_objects("media", &ListQuery::default()).await?;
+        let keys = page
+            .objects
+            .iter()
+            .map(|o| o.key.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys, vec!["song.mp3", "videos/movie.mp4"]);
+        assert_eq!(page.objects[0].size, 11);
+        assert_eq!(page.next_cursor, None);
+        assert!(
+            storage
+                .list_objects("missing", &ListQuery::default())
+                .await
+                .is_err()
+        );
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_list_objects_delimiter() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+        let query = ListQuery {
+            delimiter: Some(String::from("/")),
+            ..Default::default()
+        };
+        let page = storage.list_objects("media", &query).await?;
+
+        assert_eq!(page.objects.len(), 1);
+        assert_eq!(page.objects[0].key, "song.mp3");
+        assert_eq!(page.prefixes, vec!["videos/"]);
+
+        let query = ListQuery {
+            prefix: Some(String::from("videos/")),
+            ..query
+        };
+        let page = storage.list_objects("media", &query).await?;
+        assert_eq!(page.objects[0].key, "videos/movie.mp4");
+        assert!(page.prefixes.is_empty());
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_paginate() {
+        let keys = ["a", "b/1", "b/2", "c", "d"].map(String::from);
+        let mut query = ListQuery {
+            delimiter: Some(String::from("/")),
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let (keys_1, prefixes, cursor) = paginate(keys.clone().into_iter(), &query);
+        assert_eq!(keys_1, vec!["a"]);
+        assert_eq!(prefixes, vec!["b/"]);
+        assert_eq!(cursor.as_deref(), Some("b/"));
+
+        query.cursor = cursor;
+        let (keys_2, prefixes, cursor) = paginate(keys.into_iter(), &query);
+        assert_eq!(keys_2, vec!["c", "d"]);
+        assert!(prefixes.is_empty());
+        assert_eq!(cursor, None);
+    }
+
+    #[actix_web::test]
+    async fn test_head_object() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+        let info = storage.head_object("media", "videos/movie.mp4").await?;
+
+        assert_eq!(info.key, "videos/movie.mp4");
+        assert_eq!(info.size, 5);
+        assert_eq!(info.content_type.as_deref(), Some("video/mp4"));
+        assert!(info.etag.is_some());
+        assert!(info.last_modified.is_some());
+        assert!(storage.head_object("media", "videos").await.is_err());
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_read_range() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+
+        let data = collect(storage.read_range("media", "song.mp3", 6, 1337).await?).await?;
+        assert_eq!(data.as_ref(), b"xbyte");
+        assert!(
+            storage
+                .read_range("media", "song.mp3", 11, 1)
+                .await
+                .is_err()
+        );
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_path_traversal() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+
+        assert!(
+            storage
+                .read_range("media", "../media/song.mp3", 0, 1)
+                .await
+                .is_err()
+        );
+        assert!(storage.read_range("..", "etc/passwd", 0, 1).await.is_err());
+        assert!(
+            storage
+                .list_objects("/", &ListQuery::default())
+                .await
+                .is_err()
+        );
+
+        fs::remove_dir_all(storage.root())?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn test_symlink_escape() -> anyhow::Result<()> {
+        let storage = temp_storage()?;
+        let outside = std::env::temp_dir().join(format!("xbyte-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&outside)?;
+        fs::write(outside.join("secret.txt"), b"secret")?;
+
+        let media = storage.root().join("media");
+        std::os::unix::fs::symlink(outside.join("secret.txt"), media.join("secret.txt"))?;
+        std::os::unix::fs::symlink(&outside, media.join("outside"))?;
+        std::os::unix::fs::symlink(media.join("song.mp3"), media.join("alias.mp3"))?;
+
+        // Links leaving the root are neither read nor listed
+        assert!(
+            storage
+                .read_range("media", "secret.txt", 0, 1)
+                .await
+                .is_err()
+        );
+        assert!(
+            storage
+                .head_object("media", "outside/secret.txt")
+                .await
+                .is_err()
+        );
+        let page = storage.list_objects("media", &ListQuery::default()).await?;
+        let keys = page.objects.iter().map(|o| o.key.as_str());
+        assert_eq!(
+            keys.collect::<Vec<_>>(),
+            vec!["alias.mp3", "song.mp3", "videos/movie.mp4"]
+        );
+
+        // Links staying inside the root are served
+        let data = collect(storage.read_range("media", "alias.mp3", 6, 5).await?).await?;
+        assert_eq!(data.as_ref(), b"xbyte");
+
+        fs::remove_dir_all(storage.root())?;
+        fs::remove_dir_all(&outside)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+mod azure;
+mod cache;
+mod gcs;
+mod local;
+
+pub use azure::AzureBlobStorage;
+pub use cache::BackendCache;
+pub use gcs::GcsStorage;
+pub use local::LocalStorage;
+
+use crate::XByteS3;
+use crate::metrics::METRICS;
+use actix_web::web::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
+use prometheus::HistogramTimer;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use utoipa::{IntoParams, ToSchema};
+
+/// A stream of object bytes
+pub type ByteStream = Pin<Box<dyn Stream<Item = anyhow::Result<Bytes>> + Send>>;
+
+/// The default number of entries per listing page
+const DEFAULT_LIST_LIMIT: u32 = 1000;
+/// The maximum number of entries per listing page
+const MAX_LIST_LIMIT: u32 = 1000;
+
+/// The metadata of a stored object
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectInfo {
+    /// The object key
+    pub key: String,
+    /// The size in bytes
+    pub size: u64,
+    /// The last modification time (RFC 3339)
+    pub last_modified: Option<String>,
+    /// The entity tag
+    pub etag: Option<String>,
+    /// The MIME type
+    pub content_type: Option<String>,
+}
+
+/// The options of an object listing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    /// Only list keys starting with the prefix
+    pub prefix: Option<String>,
+    /// Group keys sharing a prefix up to the delimiter (e.g. `/` for folders)
+    pub delimiter: Option<String>,
+    /// The opaque cursor returned by the previous page
+    pub cursor: Option<String>,
+    /// The maximum number of entries in the page
+    pub limit: Option<u32>,
+}
+
+impl ListQuery {
+    /// The page size, clamped to the supported range
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .clamp(1, MAX_LIST_LIMIT)
+    }
+
+    /// The prefix, treating an empty one as none
+    pub(crate) fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref().filter(|p| !p.is_empty())
+    }
+
+    /// The delimiter, treating an empty one as none
+    pub(crate) fn delimiter(&self) -> Option<&str> {
+        self.delimiter.as_deref().filter(|d| !d.is_empty())
+    }
+}
+
+/// A page of an object listing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectPage {
+    /// The objects of the page
+    pub objects: Vec<ObjectInfo>,
+    /// The common prefixes grouped by the delimiter
+    pub prefixes: Vec<String>,
+    /// The cursor of the next page, if there is one
+    pub next_cursor: Option<String>,
+}
+
+/// A storage provider that content can be served from
+pub trait StorageBackend {
+    /// List all containers (e.g. buckets)
+    fn list_containers(&self) -> impl Future<Output = anyhow::Result<Vec<String>>> + Send;
+    /// Check that the container exists and is accessible
+    fn head_container(&self, container: &str) -> impl Future<Output = anyhow::Result<()>> + Send;
+    /// List a page of objects in a container
+    fn list_objects(
+        &self,
+        container: &str,
+        query: &ListQuery,
+    ) -> impl Future<Output = anyhow::Result<ObjectPage>> + Send;
+    /// Get the metadata of an object
+    fn head_object(
+        &self,
+        container: &str,
+        key: &str,
+    ) -> impl Future<Output = anyhow::Result<ObjectInfo>> + Send;
+    /// Read a range of an object as a stream
+    fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Output = anyhow::Result<ByteStream>> + Send;
+}
+
+/// A storage backend resolved from a client's [`crate::Storage`]
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// AWS S3 or an S3-compatible provider
+    S3(XByteS3),
+    /// Google Cloud Storage
+    Gcs(GcsStorage),
+    /// Azure Blob Storage
+    Azure(AzureBlobStorage),
+    /// The local filesystem
+    Local(LocalStorage),
+}
+
+impl Backend {
+    /// The name of the storage provider
+    pub fn provider(&self) -> &'static str {
+        match self {
+            Self::S3(_) => "s3",
+            Self::Gcs(_) => "gcs",
+            Self::Azure(_) => "azure",
+            Self::Local(_) => "local",
+        }
+    }
+
+    /// Time a storage operation until the timer is dropped
+    fn timer(&self, operation: &str) -> HistogramTimer {
+        METRICS
+            .storage_duration
+            .with_label_values(&[self.provider(), operation])
+            .start_timer()
+    }
+}
+
+impl StorageBackend for Backend {
+    async fn list_containers(&self) -> anyhow::Result<Vec<String>> {
+        match self {
+            Self::S3(s3) => s3.list_containers().await,
+            Self::Gcs(gcs) => gcs.list_containers().await,
+            Self::Azure(azure) => azure.list_containers().await,
+            Self::Local(local) => local.list_containers().await,
+        }
+    }
+
+    async fn head_container(&self, container: &str) -> anyhow::Result<()> {
+        match self {
+            Self::S3(s3) => s3.head_container(container).await,
+            Self::Gcs(gcs) => gcs.head_container(container).await,
+            Self::Azure(azure) => azure.head_container(container).await,
+            Self::Local(local) => local.head_container(container).await,
+        }
+    }
+
+    async fn list_objects(&self, container: &str, query: &ListQuery) -> anyhow::Result<ObjectPage> {
+        let _timer = self.timer("list_objects");
+        match self {
+            Self::S3(s3) => StorageBackend::list_objects(s3, container, query).await,
+            Self::Gcs(gcs) => gcs.list_objects(container, query).await,
+            Self::Azure(azure) => azure.list_objects(container, query).await,
+            Self::Local(local) => local.list_objects(container, query).await,
+        }
+    }
+
+    async fn head_object(&self, container: &str, key: &str) -> anyhow::Result<ObjectInfo> {
+        let _timer = self.timer("head_object");
+        match self {
+            Self::S3(s3) => s3.head_object(container, key).await,
+            Self::Gcs(gcs) => gcs.head_object(container, key).await,
+            Self::Azure(azure) => azure.head_object(container, key).await,
+            Self::Local(local) => local.head_object(container, key).await,
+        }
+    }
+
+    async fn read_range(
+        &self,
+        container: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<ByteStream> {
+        let _timer = self.timer("read_range");
+        match self {
+            Self::S3(s3) => s3.read_range(container, key, offset, len).await,
+            Self::Gcs(gcs) => gcs.read_range(container, key, offset, len).await,
+            Self::Azure(azure) => azure.read_range(container, key, offset, len).await,
+            Self::Local(local) => local.read_range(container, key, offset, len).await,
+        }
+    }
+}
+
+/// Guess the MIME type of an object from its key
+pub(crate) fn guess_content_type(key: &str) -> Option<String> {
+    mime_guess::from_path(key)
+        .first()
+        .map(|m| m.essence_str().to_string())
+}
+
+/// Collect a byte stream into a single buffer
+pub async fn collect(mut stream: ByteStream) -> anyhow::Result<Bytes> {
+    let mut data = BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk?);
+    }
+
+    Ok(data.freeze())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    pub(crate) use super::cache::tests::offline_sts;
+    pub(crate) use super::local::tests::temp_storage;
+}
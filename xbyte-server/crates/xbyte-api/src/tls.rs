@@ -0,0 +1,212 @@
+use crate::config::TlsConfig;
+use anyhow::Context;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// The interval between checks of the certificate files
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The last modification of the certificate and key files
+type Modified = (Option<SystemTime>, Option<SystemTime>);
+
+/// The certificate served to every client, swapped when its files change
+///
+/// Connections already open keep the certificate they were handshaken with.
+#[derive(Debug)]
+pub(crate) struct CertResolver {
+    /// The certificate and key files
+    config: TlsConfig,
+    /// The cryptography of the handshakes
+    provider: Arc<CryptoProvider>,
+    /// The certificate served to new connections
+    current: RwLock<Arc<CertifiedKey>>,
+    /// The modification of the files when they were last loaded
+    modified: Mutex<Modified>,
+}
+
+impl CertResolver {
+    /// Load the certificate of the configuration
+    pub fn new(config: &TlsConfig) -> anyhow::Result<Self> {
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let modified = Self::modified(config);
+        let current = Self::load(config, &provider)?;
+
+        Ok(Self {
+            config: config.clone(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// The last modification of the files, `None` for a file that cannot be read
+    fn modified(config: &TlsConfig) -> Modified {
+        let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+        (modified(&config.cert_path), modified(&config.key_path))
+    }
+
+    /// Read the PEM certificate chain and private key
+    fn load(config: &TlsConfig, provider: &CryptoProvider) -> anyhow::Result<CertifiedKey> {
+        let cert = &config.cert_path;
+        let chain = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("failed to read the certificate {}", cert.display()))?;
+        anyhow::ensure!(!chain.is_empty(), "no certificate in {}", cert.display());
+
+        let key = &config.key_path;
+        let key = PrivateKeyDer::from_pem_file(key)
+            .with_context(|| format!("failed to read the private key {}", key.display()))?;
+
+        CertifiedKey::from_der(chain, key, provider)
+            .context("the key does not match the certificate")
+    }
+
+    /// Reload the certificate when its files changed, returning whether it was swapped
+    ///
+    /// A certificate that fails to load keeps the previous one in place and is retried.
+    pub fn reload(&self) -> anyhow::Result<bool> {
+        let modified = Self::modified(&self.config);
+        let mut last = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+        if modified == *last {
+            return Ok(false);
+        }
+
+        let certified = Self::load(&self.config, &self.provider)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(certified);
+        *last = modified;
+        Ok(true)
+    }
+
+    /// Check the files periodically, reloading the certificate when they change
+    pub async fn watch(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(RELOAD_INTERVAL);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            match self.reload() {
+                Ok(true) => tracing::info!("Reloaded the TLS certificate"),
+                Ok(false) => {}
+                Err(error) => tracing::error!(?error, "Failed to reload the TLS certificate"),
+            }
+        }
+    }
+
+    /// The TLS configuration serving the certificate, HTTP/2 is negotiated by actix over ALPN
+    pub fn server_config(self: Arc<Self>) -> anyhow::Result<rustls::ServerConfig> {
+        let config = rustls::ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(self);
+        Ok(config)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+        Some(current.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpResponse, HttpServer, web};
+    use rustls::pki_types::ServerName;
+    use std::fs::File;
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpStream};
+
+    /// A self-signed certificate for localhost, as PEM certificate and key
+    fn self_signed() -> (CertificateDer<'static>, String, String) {
+        let rcgen::CertifiedKey { cert, signing_key } =
+            rcgen::generate_simple_self_signed([String::from("localhost")]).unwrap();
+        (cert.der().clone(), cert.pem(), signing_key.serialize_pem())
+    }
+
+    /// Write a certificate, moving its modification time forward
+    fn write(config: &TlsConfig, cert: &str, key: &str, age: u64) {
+        let modified = SystemTime::now() + Duration::from_secs(age);
+        for (path, content) in [(&config.cert_path, cert), (&config.key_path, key)] {
+            let mut file = File::create(path).unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+    }
+
+    /// Handshake with the server, returning the certificate it served and the protocol agreed on
+    fn handshake(addr: SocketAddr, root: &CertificateDer<'static>) -> (Vec<u8>, Option<Vec<u8>>) {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(root.clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let mut config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut client = rustls::ClientConnection::new(Arc::new(config), name).unwrap();
+        let mut socket = TcpStream::connect(addr).unwrap();
+        while client.is_handshaking() {
+            client.complete_io(&mut socket).unwrap();
+        }
+
+        let served = client.peer_certificates().unwrap()[0].to_vec();
+        (served, client.alpn_protocol().map(<[u8]>::to_vec))
+    }
+
+    #[actix_web::test]
+    async fn test_reload_certificate() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("xbyte-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let config = TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        let (first, cert, key) = self_signed();
+        write(&config, &cert, &key, 0);
+
+        let resolver = Arc::new(CertResolver::new(&config)?);
+        let server = HttpServer::new(|| App::new().default_service(web::to(HttpResponse::Ok)))
+            .workers(1)
+            .disable_signals()
+            .bind_rustls_0_23("127.0.0.1:0", resolver.clone().server_config()?)?;
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        // HTTP/2 is negotiated with the loaded certificate
+        let root = first.clone();
+        let (served, protocol) =
+            tokio::task::spawn_blocking(move || handshake(addr, &root)).await?;
+        assert_eq!(served, first.to_vec());
+        assert_eq!(protocol.as_deref(), Some(&b"h2"[..]));
+        assert!(!resolver.reload()?);
+
+        // A broken certificate keeps the previous one
+        write(&config, "not a certificate", &key, 10);
+        assert!(resolver.reload().is_err());
+
+        // New connections get the renewed certificate
+        let (second, cert, key) = self_signed();
+        write(&config, &cert, &key, 20);
+        assert!(resolver.reload()?);
+        let root = second.clone();
+        let (served, _) = tokio::task::spawn_blocking(move || handshake(addr, &root)).await?;
+        assert_eq!(served, second.to_vec());
+
+        handle.stop(false).await;
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use tracing::Instrument;
+use tracing::field::Empty;
+
+/// The header correlating a request across logs and services
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// The longest request ID accepted from clients
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Keep the request ID of the client when it is sane, otherwise assign a new one
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// The middleware opening a span per request, which handlers fill with the bucket, object,
+/// payer and amount, and echoing the `X-Request-Id` header
+pub(crate) async fn request_span(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let id = request_id(&req);
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.path(),
+        bucket = Empty,
+        object = Empty,
+        payer = Empty,
+        amount = Empty,
+    );
+    crate::propagation::continue_trace(&span, req.headers());
+
+    let mut res = next.call(req).instrument(span).await?;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{App, HttpResponse, test, web};
+
+    #[actix_web::test]
+    async fn test_request_id() {
+        let app = App::new()
+            .default_service(web::to(HttpResponse::Ok))
+            .wrap(from_fn(request_span));
+        let app = test::init_service(app).await;
+
+        // The ID of the client is propagated
+        let req = test::TestRequest::get()
+            .insert_header((REQUEST_ID_HEADER, "edge-1234"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(&REQUEST_ID_HEADER).unwrap(), "edge-1234");
+
+        // Missing or unusable IDs are replaced
+        for id in ["", "with space", &"a".repeat(MAX_REQUEST_ID_LEN + 1)] {
+            let req = test::TestRequest::get()
+                .insert_header((REQUEST_ID_HEADER, id))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            let assigned = res.headers().get(&REQUEST_ID_HEADER).unwrap();
+            assert!(uuid::Uuid::parse_str(assigned.to_str().unwrap()).is_ok());
+        }
+    }
+}
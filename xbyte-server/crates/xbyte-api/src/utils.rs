@@ -19,6 +19,18 @@ pub enum ResultAPI<D, E> {
     PaymentRequired(E),
 }
 
+/// An error that knows the HTTP status it is returned with
+pub trait ErrorStatus {
+    /// The HTTP status of the error
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+impl ErrorStatus for () {}
+impl ErrorStatus for &str {}
+impl ErrorStatus for String {}
+
 impl<D> ResultAPI<D, ()> {
     /// Create a successful result
     pub fn success(data: D) -> Self {
@@ -46,14 +58,6 @@ impl<D, E> ResultAPI<D, E> {
     pub fn payment_required(payment: E) -> Self {
         Self::PaymentRequired(payment)
     }
-    /// Get Status Code
-    pub fn get_status(&self) -> StatusCode {
-        match self {
-            Self::Success(_) => StatusCode::OK,
-            Self::Error(_) => StatusCode::BAD_REQUEST,
-            Self::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
-        }
-    }
     /// Get the data
     pub fn get_data(&self) -> Option<&D> {
         match self {
@@ -72,10 +76,21 @@ impl<D, E> ResultAPI<D, E> {
     }
 }
 
+impl<D, E: ErrorStatus> ResultAPI<D, E> {
+    /// Get Status Code
+    pub fn get_status(&self) -> StatusCode {
+        match self {
+            Self::Success(_) => StatusCode::OK,
+            Self::Error(e) => e.status(),
+            Self::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+        }
+    }
+}
+
 impl<D, E> actix_web::Responder for ResultAPI<D, E>
 where
     D: Serialize,
-    E: Serialize,
+    E: Serialize + ErrorStatus,
 {
     type Body = actix_web::body::BoxBody;
 
@@ -92,6 +107,16 @@ pub fn calculate_price(price: f32, length: f32) -> u64 {
     (price * price_per_mb) as u64
 }
 
+/// Clamp a range to the object size, returning the deliverable length or `None` when the
+/// range is empty or starts past the end of the object
+pub fn clamp_range(offset: u64, length: u64, size: u64) -> Option<u64> {
+    let available = size.checked_sub(offset)?;
+    match length.min(available) {
+        0 => None,
+        length => Some(length),
+    }
+}
+
 /// Calculate the range header for a given offset and length
 pub fn calculate_range_header(offset: u64, len: u64) -> Option<String> {
     let end = offset.checked_add(len)?.checked_sub(1)?;
@@ -0,0 +1,113 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, LINK};
+use actix_web::middleware::{Next, from_fn};
+use actix_web::web;
+
+/// The prefix of the current version of the API
+pub const API_PREFIX: &str = "/v1";
+/// The versions of the API served, the first one being current
+pub const API_VERSIONS: [&str; 1] = ["v1"];
+/// The header marking the unversioned aliases as deprecated
+pub const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+/// When the unversioned aliases were deprecated, as an RFC 9745 date (2026-10-19 UTC)
+const DEPRECATED_AT: &str = "@1792368000";
+
+/// Mount the routes under the version prefix, and at the root as well
+///
+/// The infrastructure routes (health, metrics, the spec) stay at the root for probes and
+/// scrapers, while the root aliases of the API routes are deprecated.
+pub(crate) fn mount(
+    config: &mut web::ServiceConfig,
+    infra: fn(&mut web::ServiceConfig),
+    api: fn(&mut web::ServiceConfig),
+) {
+    config
+        .service(web::redirect(API_PREFIX, format!("{API_PREFIX}/")))
+        .service(web::scope(API_PREFIX).configure(infra).configure(api))
+        .configure(infra)
+        .service(web::scope("").wrap(from_fn(deprecated)).configure(api));
+}
+
+/// The middleware flagging an unversioned alias, pointing to its versioned successor
+pub(crate) async fn deprecated(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let successor = format!("<{API_PREFIX}{}>; rel=\"successor-version\"", req.path());
+
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    headers.insert(DEPRECATION_HEADER, HeaderValue::from_static(DEPRECATED_AT));
+    if let Ok(value) = HeaderValue::from_str(&successor) {
+        headers.insert(LINK, value);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthRoute;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::LOCATION;
+    use actix_web::{App, HttpResponse, test};
+
+    fn infra(config: &mut web::ServiceConfig) {
+        config
+            .service(HealthRoute::Status)
+            .service(HealthRoute::Index);
+    }
+
+    fn api(config: &mut web::ServiceConfig) {
+        config.route("/price", web::get().to(HttpResponse::Ok));
+    }
+
+    #[actix_web::test]
+    async fn test_versioned_routes() {
+        let app = App::new().configure(|config| mount(config, infra, api));
+        let app = test::init_service(app).await;
+
+        // The versioned routes are current
+        for uri in ["/v1/health", "/v1/price"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(res.headers().get(DEPRECATION_HEADER).is_none());
+        }
+
+        // The unversioned API aliases still answer, flagged with their successor
+        let req = test::TestRequest::get().uri("/price").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(DEPRECATION_HEADER).unwrap(),
+            "@1792368000"
+        );
+        assert_eq!(
+            res.headers().get(LINK).unwrap(),
+            "</v1/price>; rel=\"successor-version\""
+        );
+
+        // Probes keep the unversioned infrastructure routes, which are not deprecated
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get(DEPRECATION_HEADER).is_none());
+        assert!(res.headers().get(LINK).is_none());
+
+        // The index is served at the version root
+        let req = test::TestRequest::get().uri("/v1").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/v1/");
+        let req = test::TestRequest::get().uri("/v1/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Unknown versioned paths are not served by the aliases
+        let req = test::TestRequest::get().uri("/v1/v1/health").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -2,10 +2,22 @@ use base64::{Engine, engine::general_purpose};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::future::{Ready, ready};
+use std::time::Duration;
 use url::Url;
+use utoipa::ToSchema;
 
 /// The URL of the X402 facilitator
+#[cfg(not(test))]
 const FACILITATOR_URL: &str = "https://www.x402.org/facilitator";
+/// An unreachable facilitator, so tests never reach the network
+#[cfg(test)]
+const FACILITATOR_URL: &str = "http://127.0.0.1:1/facilitator";
+/// The header carrying the payment payload
+pub const PAYMENT_HEADER: &str = "X-Payment";
+/// The header carrying the settlement response
+pub const PAYMENT_RESPONSE_HEADER: &str = "X-PAYMENT-RESPONSE";
+/// The version of the x402 protocol of the challenges
+pub const X402_VERSION: u32 = 1;
 
 /// The configuration for the X402 state
 pub struct ConfigX402<S> {
@@ -39,18 +51,32 @@ impl ConfigX402<&'static str> {
 }
 
 /// The payment request from x402 server
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
+#[schema(bound = "")]
 pub struct PaymentRequest<S, T> {
+    #[schema(value_type = String, example = "exact")]
     pub scheme: S,
+    #[schema(value_type = String, example = "base-sepolia")]
     pub network: S,
+    /// The price in token units
+    #[schema(value_type = String)]
     pub max_amount_required: T,
+    #[schema(value_type = String)]
     pub resource: Url,
+    #[schema(value_type = Option<String>)]
     pub description: Option<S>,
+    #[schema(value_type = String)]
     pub mime_type: S,
+    /// The vault of the bucket owner
+    #[schema(value_type = String)]
     pub pay_to: T,
     pub max_timeout_seconds: u64,
+    /// The EIP-712 domain of the token
+    #[schema(value_type = HashMap<String, String>)]
     pub extra: HashMap<S, S>,
+    /// The token contract
+    #[schema(value_type = String)]
     pub asset: S,
 }
 
@@ -81,22 +107,22 @@ where
     }
 }
 
-#[derive(Debug, Serialize)]
+/// The x402 challenge returned with `402 Payment Required`
+#[derive(Debug, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
+#[schema(bound = "")]
 pub struct X402Response<S, T> {
     pub x402_version: u32,
+    #[schema(value_type = Vec<PaymentRequest<String, String>>)]
     pub accepts: Vec<PaymentRequest<S, T>>,
 }
 
 impl<S: Copy, T: Clone> X402Response<S, T> {
     /// Create a new X402Response
     pub fn new(payment_requests: &[PaymentRequest<S, T>]) -> Self {
-        let x402_version = 1;
-        let accepts = payment_requests.to_vec();
-
         Self {
-            x402_version,
-            accepts,
+            x402_version: X402_VERSION,
+            accepts: payment_requests.to_vec(),
         }
     }
 }
@@ -147,17 +173,85 @@ impl<S: Copy + Serialize, T: Clone + Serialize> FacilitatorRequest<S, T> {
     /// Verify the payment
     pub fn verify(&self) -> anyhow::Result<FacilitatorResponse> {
         let url = format!("{FACILITATOR_URL}/verify");
-        let response = ureq::post(&url).send_json(self)?;
+        let response = traced(ureq::post(&url)).send_json(self)?;
         Ok(response.into_body().read_json()?)
     }
     /// Settle the payment
     pub fn settle(&self) -> anyhow::Result<FacilitatorResponse> {
-        let url = format!("{FACILITATOR_URL}/settle");
-        let response = ureq::post(&url).send_json(self)?;
-        Ok(response.into_body().read_json()?)
+        settle(self)
+    }
+}
+
+/// Settle a payment with a facilitator request, e.g. one kept to retry a settlement
+pub fn settle(request: &impl Serialize) -> anyhow::Result<FacilitatorResponse> {
+    let url = format!("{FACILITATOR_URL}/settle");
+    let response = traced(ureq::post(&url)).send_json(request)?;
+    Ok(response.into_body().read_json()?)
+}
+
+/// Whether a failed facilitator call rejected the payment, rather than the facilitator failing
+pub fn is_rejection(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<ureq::Error>() {
+        Some(ureq::Error::StatusCode(status)) => (400..500).contains(status) && *status != 429,
+        _ => false,
     }
 }
 
+/// Add the trace context of the current span to a facilitator request
+fn traced<B>(request: ureq::RequestBuilder<B>) -> ureq::RequestBuilder<B> {
+    crate::trace_headers()
+        .into_iter()
+        .fold(request, |request, (name, value)| {
+            request.header(name, value)
+        })
+}
+
+/// Get the payment kinds supported by the facilitator, failing after the timeout
+pub fn supported(timeout: Duration) -> anyhow::Result<serde_json::Value> {
+    let url = format!("{FACILITATOR_URL}/supported");
+    let response = traced(ureq::get(&url))
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .call()?;
+    Ok(response.into_body().read_json()?)
+}
+
+/// The status of a received payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PaymentStatus {
+    /// Verified and being settled
+    Pending,
+    /// Settled by the facilitator
+    Settled,
+    /// Rejected by the facilitator during settlement
+    Failed,
+    /// Never settled because the content could not be delivered
+    Voided,
+}
+
+/// A payment received for a range of an object, keyed by its authorization nonce
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    /// The bucket of the object
+    pub bucket: String,
+    /// The object paid for
+    pub object: String,
+    /// The payer address
+    pub payer: String,
+    /// The authorized amount
+    pub amount: String,
+    /// The settlement status
+    pub status: PaymentStatus,
+    /// Why the payment was not settled
+    pub reason: Option<String>,
+    /// The facilitator request of a pending payment, kept to retry an interrupted settlement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<serde_json::Value>,
+}
+
 /// The payment extractor from the client
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -194,7 +288,7 @@ impl actix_web::FromRequest for PaymentExtractor {
 
     fn from_request(req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
         // Get payment authorization header
-        let Some(header_value) = req.headers().get("X-Payment") else {
+        let Some(header_value) = req.headers().get(PAYMENT_HEADER) else {
             let error = actix_web::error::ErrorPaymentRequired("Missing X-Payment header");
             return ready(Err(error));
         };
@@ -212,3 +306,22 @@ impl actix_web::FromRequest for PaymentExtractor {
         ready(Ok(authorization))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_rejection() {
+        let status = |code| anyhow::Error::from(ureq::Error::StatusCode(code));
+
+        // The facilitator refusing the payment is a rejection
+        assert!(is_rejection(&status(400)));
+        // Outages and throttling are not the payer's fault
+        assert!(!is_rejection(&status(429)));
+        assert!(!is_rejection(&status(503)));
+        assert!(!is_rejection(&anyhow::Error::from(
+            ureq::Error::ConnectionFailed
+        )));
+    }
+}
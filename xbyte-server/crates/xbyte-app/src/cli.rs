@@ -0,0 +1,319 @@
+use alloy_primitives::Address;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use xbyte_api::{
+    BackendCache, Client, Database, MemoryDB, RegisterRequest, ServerConfig, SetPriceRequest,
+    Storage,
+};
+use xbyte_evm::Factory;
+
+/// The config file read when `--config` and `XBYTE_CONFIG` are not set
+const DEFAULT_CONFIG: &str = "xbyte.toml";
+
+/// The xByte server and its administration commands
+///
+/// The client, price, bucket and db commands edit the database snapshot directly, stop the
+/// server first as its snapshots would overwrite the changes.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// The config file, `xbyte.toml` when it exists
+    #[arg(long, short, global = true, env = "XBYTE_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// The command to run, the server by default
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// The commands of the CLI
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the API server
+    Serve,
+    /// Print the vault address of an owner
+    ComputeVault {
+        /// The wallet owning the vault
+        owner: Address,
+    },
+    /// Manage the content platforms
+    #[command(subcommand)]
+    Client(ClientCommand),
+    /// Manage the object prices
+    #[command(subcommand)]
+    Price(PriceCommand),
+    /// Manage the buckets of the clients
+    #[command(subcommand)]
+    Bucket(BucketCommand),
+    /// Manage the database snapshot
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+/// The client commands
+#[derive(Debug, Subcommand)]
+pub enum ClientCommand {
+    /// Create a client, its vault derived from the wallet
+    Create {
+        /// The nickname of the client
+        name: String,
+        /// The wallet of the client
+        wallet: Address,
+    },
+    /// List every client as JSON
+    List,
+}
+
+/// The price commands
+#[derive(Debug, Subcommand)]
+pub enum PriceCommand {
+    /// Set the price of an object
+    Set {
+        bucket: String,
+        object: String,
+        /// The price in USDC / 1MB
+        price: u64,
+    },
+    /// Print the price of an object
+    Get { bucket: String, object: String },
+    /// Set the prices of a JSON file, e.g. `[{"bucket": "b", "object": "o", "price": 10}]`
+    Import { file: PathBuf },
+}
+
+/// The bucket commands
+#[derive(Debug, Subcommand)]
+pub enum BucketCommand {
+    /// Register buckets of a client, checking they are accessible through the storage
+    Register {
+        /// The wallet of the client
+        client: Address,
+        /// The JSON file of the storage, e.g. `{"s3": {"role_arn": "...", "region": "..."}}`
+        #[arg(long)]
+        storage: PathBuf,
+        /// The buckets to register
+        #[arg(required = true)]
+        buckets: Vec<String>,
+    },
+}
+
+/// The database commands
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Upgrade the snapshot to the current format, creating it if missing
+    Migrate,
+}
+
+/// The configuration commands
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print it with the environment applied
+    Check,
+}
+
+impl Cli {
+    /// The config file given, or the default one when it exists
+    pub fn config_path(&self) -> Option<PathBuf> {
+        match &self.config {
+            Some(path) => Some(path.clone()),
+            None => Some(PathBuf::from(DEFAULT_CONFIG)).filter(|p| p.exists()),
+        }
+    }
+}
+
+/// Run a command, administration commands print their result
+pub async fn run(command: Command, config: ServerConfig) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => crate::serve(config).await?,
+        Command::ComputeVault { owner } => println!("{}", Factory::<()>::compute_vault(owner)),
+        Command::Client(command) => edit(&config, |db| client(db, command))?,
+        Command::Price(command) => edit(&config, |db| price(db, command))?,
+        Command::Bucket(BucketCommand::Register {
+            client,
+            storage,
+            buckets,
+        }) => {
+            let storage: Storage<String> = read_json(&storage)?;
+            let cache = BackendCache::from_config(&config).await;
+            let request = RegisterRequest {
+                storage,
+                client,
+                buckets,
+            };
+
+            let path = database(&config)?;
+            let db = MemoryDB::load(path)?;
+            xbyte_api::register_buckets(&db, &cache, request)
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            db.save(path)?;
+            println!("Registered the buckets of {client}");
+        }
+        Command::Db(DbCommand::Migrate) => {
+            let path = database(&config)?;
+            match MemoryDB::migrate(path)? {
+                Some(version) => println!("Migrated {} from version {version}", path.display()),
+                None => println!("Created {}", path.display()),
+            }
+        }
+        Command::Config(ConfigCommand::Check) => {
+            config.validate()?;
+            print!("{}", toml::to_string_pretty(&config)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The snapshot file of the database
+fn database(config: &ServerConfig) -> anyhow::Result<&Path> {
+    config
+        .database
+        .path()
+        .context("the database is kept in memory, set database.path to manage it")
+}
+
+/// Apply a change to the database snapshot and save it
+fn edit(
+    config: &ServerConfig,
+    change: impl FnOnce(&MemoryDB) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let path = database(config)?;
+    let db = MemoryDB::load(path)
+        .with_context(|| format!("failed to load the database {}", path.display()))?;
+    change(&db)?;
+    db.save(path)
+}
+
+/// Read a JSON file
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let content =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_slice(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Run a client command
+fn client(db: &MemoryDB, command: ClientCommand) -> anyhow::Result<()> {
+    match command {
+        ClientCommand::Create { name, wallet } => {
+            anyhow::ensure!(
+                db.get_client(&wallet).is_err(),
+                "client {wallet} already exists"
+            );
+            let client = Client::new(name, wallet);
+            db.set_client(wallet, client.clone())?;
+            println!("{}", serde_json::to_string_pretty(&client)?);
+        }
+        ClientCommand::List => {
+            let mut clients = db.get_all_clients()?;
+            clients.sort_by_key(|c| c.id);
+            println!("{}", serde_json::to_string_pretty(&clients)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a price command
+fn price(db: &MemoryDB, command: PriceCommand) -> anyhow::Result<()> {
+    match command {
+        PriceCommand::Set {
+            bucket,
+            object,
+            price,
+        } => db.set_price((bucket, object), price)?,
+        PriceCommand::Get { bucket, object } => println!("{}", db.get_price(&(bucket, object))?),
+        PriceCommand::Import { file } => {
+            let prices: Vec<SetPriceRequest> = read_json(&file)?;
+            let count = prices.len();
+            for SetPriceRequest {
+                bucket,
+                object,
+                price,
+            } in prices
+            {
+                db.set_price((bucket, object), price)?;
+            }
+            println!("Imported {count} prices");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xbyte_api::DatabaseConfig;
+
+    /// Parse and run a command against the configuration
+    async fn cli(config: &ServerConfig, args: &[&str]) -> anyhow::Result<()> {
+        let cli = Cli::try_parse_from(std::iter::once("xbyte_app").chain(args.iter().copied()))?;
+        run(cli.command.unwrap(), config.clone()).await
+    }
+
+    #[tokio::test]
+    async fn test_manage_database() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("xbyte-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("xbyte.json");
+        let config = ServerConfig {
+            database: DatabaseConfig::File {
+                path: path.clone(),
+                snapshot_interval: 30,
+            },
+            ..Default::default()
+        };
+        let wallet = "0x1234567890123456789012345678901234567890";
+
+        cli(&config, &["db", "migrate"]).await?;
+        cli(&config, &["client", "create", "platformA", wallet]).await?;
+        assert!(
+            cli(&config, &["client", "create", "platformB", wallet])
+                .await
+                .is_err()
+        );
+        cli(&config, &["price", "set", "media", "song.mp3", "10"]).await?;
+
+        let prices = dir.join("prices.json");
+        let import = r#"[{"bucket": "media", "object": "clip.mp4", "price": 25}]"#;
+        std::fs::write(&prices, import)?;
+        cli(&config, &["price", "import", prices.to_str().unwrap()]).await?;
+
+        // The changes are saved to the snapshot
+        let db = MemoryDB::load(&path)?;
+        assert_eq!(db.get_client(&wallet.parse()?)?.name, "platformA");
+        assert_eq!(db.get_price(&("media".into(), "song.mp3".into()))?, 10);
+        assert_eq!(db.get_price(&("media".into(), "clip.mp4".into()))?, 25);
+        assert!(
+            cli(&config, &["price", "get", "media", "missing"])
+                .await
+                .is_err()
+        );
+
+        // An in-memory database cannot be managed
+        let memory = ServerConfig::default();
+        assert!(cli(&memory, &["client", "list"]).await.is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let cli = Cli::try_parse_from(["xbyte_app", "--config", "prod.toml"]).unwrap();
+        assert_eq!(cli.config_path(), Some(PathBuf::from("prod.toml")));
+        assert!(cli.command.is_none());
+
+        let owner = "0x1234567890123456789012345678901234567890";
+        let cli = Cli::try_parse_from(["xbyte_app", "compute-vault", owner]).unwrap();
+        assert!(matches!(cli.command, Some(Command::ComputeVault { .. })));
+
+        // Buckets are registered with their storage
+        let args = ["xbyte_app", "bucket", "register", owner, "media"];
+        assert!(Cli::try_parse_from(args).is_err());
+        assert!(Cli::try_parse_from(["xbyte_app", "compute-vault", "0x12"]).is_err());
+    }
+}
@@ -1,21 +1,94 @@
-use xbyte_api::Server;
+mod cli;
+#[cfg(feature = "otel")]
+mod otel;
+
+use anyhow::Context;
+use clap::Parser;
+use cli::{Cli, Command};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use xbyte_api::{LogConfig, LogFormat, Server, ServerConfig};
+
+/// The tracing backends to flush before exiting
+#[derive(Default)]
+struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Flush the pending spans
+    fn shutdown(self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider
+            && let Err(error) = provider.shutdown()
+        {
+            eprintln!("Failed to flush the traces: {error}");
+        }
+    }
+}
+
+/// Initialize logging, JSON lines carry the fields of the request span
+///
+/// With the `otel` feature the spans are also exported over OTLP.
+fn init_logging(config: &LogConfig) -> anyhow::Result<Telemetry> {
+    let filter = EnvFilter::try_new(&config.filter).context("invalid log filter")?;
+    let (text, json) = match config.format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => {
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true);
+            (None, Some(layer))
+        }
+    };
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json);
+
+    #[cfg(feature = "otel")]
+    {
+        let provider = otel::provider(None)?;
+        registry.with(otel::layer(&provider)).init();
+        Ok(Telemetry {
+            provider: Some(provider),
+        })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        Ok(Telemetry::default())
+    }
+}
+
+/// Validate the configuration and run the API server until it shuts down
+async fn serve(config: ServerConfig) -> anyhow::Result<()> {
+    config
+        .validate()
+        .context("failed to load the configuration")?;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
     // Initialize Logging
-    tracing_subscriber::fmt::fmt()
-        .with_env_filter("xbyte_api=debug,xbyte_app=debug")
-        .init();
+    let telemetry = init_logging(&config.log)?;
+
+    // Start the API server
+    let result = Server::from_config(config).run().await;
+    telemetry.shutdown();
+
+    result
+}
 
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     #[cfg(debug_assertions)]
     dotenv::dotenv().ok();
 
-    let rpc_url = std::env::var("RPC_URL").expect("ENV Variable RPC_URL is not set");
-    let server_addr = std::env::var("SERVER_ADDR").expect("ENV Variable SERVER_ADDR is not set");
-
-    // Start the API server
-    let server = Server::new(server_addr, rpc_url);
-    server.run().await?;
+    // Load the config file if any, the environment overrides it
+    let cli = Cli::parse();
+    let path = cli.config_path();
+    let config = ServerConfig::read(path.as_deref()).context("failed to load the configuration")?;
 
-    Ok(())
+    cli::run(cli.command.unwrap_or(Command::Serve), config).await
 }
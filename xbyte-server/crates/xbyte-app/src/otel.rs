@@ -0,0 +1,75 @@
+use anyhow::Context;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The service name reported when `OTEL_SERVICE_NAME` is not set
+const SERVICE_NAME: &str = "xbyte-server";
+
+/// Build the tracer provider exporting spans over OTLP/HTTP
+///
+/// The collector is read from `OTEL_EXPORTER_OTLP_ENDPOINT` unless an endpoint is given.
+pub fn provider(endpoint: Option<&str>) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder().with_http();
+    let exporter = match endpoint {
+        Some(endpoint) => exporter.with_endpoint(endpoint),
+        None => exporter,
+    };
+    let exporter = exporter
+        .build()
+        .context("failed to build the OTLP exporter")?;
+
+    let name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| SERVICE_NAME.to_string());
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(name).build())
+        .build())
+}
+
+/// The layer sending spans to the provider, which also installs the W3C propagator
+pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S> + use<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_export_to_collector() -> anyhow::Result<()> {
+        // A collector stand-in accepting one export
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr()?);
+        let collector = std::thread::spawn(move || -> anyhow::Result<String> {
+            let (mut stream, _) = listener.accept()?;
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer)?;
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")?;
+            Ok(String::from_utf8_lossy(&buffer[..read]).to_string())
+        });
+
+        let provider = provider(Some(&endpoint))?;
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", path = "/health").in_scope(|| {});
+        });
+        provider.shutdown()?;
+
+        let request = collector.join().unwrap()?;
+        assert!(request.starts_with("POST /v1/traces"));
+        assert!(request.contains("application/x-protobuf"));
+        Ok(())
+    }
+}
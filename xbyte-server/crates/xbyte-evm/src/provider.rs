@@ -1,8 +1,17 @@
 use crate::{Factory, Relay};
+use alloy_json_rpc::{RequestPacket, ResponsePacket};
 use alloy_primitives::Address;
 use alloy_provider::network::Ethereum;
 use alloy_provider::{DynProvider, Provider};
+use alloy_rpc_client::ClientBuilder;
+use alloy_transport::{TransportError, TransportFut};
+use http::{HeaderName, HeaderValue};
 use std::ops::Deref;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// A function returning the headers to attach to each RPC request, e.g. a trace context
+pub type HeaderFn = fn() -> Vec<(String, String)>;
 
 /// xByte EVM Client
 #[derive(Debug, Clone)]
@@ -11,8 +20,16 @@ pub struct Client(DynProvider<Ethereum>);
 impl Client {
     /// Initialize a new EVM Client
     pub fn new(url: &str) -> anyhow::Result<Self> {
+        Self::with_headers(url, Vec::new)
+    }
+
+    /// Initialize a new EVM Client attaching the headers returned by `headers` to each request
+    pub fn with_headers(url: &str, headers: HeaderFn) -> anyhow::Result<Self> {
         let url = url.parse()?;
-        let provider = DynProvider::builder().connect_http(url).erased();
+        let client = ClientBuilder::default()
+            .layer(HeaderLayer(headers))
+            .http(url);
+        let provider = DynProvider::builder().connect_client(client).erased();
         Ok(Self(provider))
     }
 
@@ -41,6 +58,63 @@ impl Deref for Client {
     }
 }
 
+/// A transport layer attaching headers to each request
+#[derive(Debug, Clone, Copy)]
+struct HeaderLayer(HeaderFn);
+
+impl<S> Layer<S> for HeaderLayer {
+    type Service = HeaderService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HeaderService {
+            inner,
+            headers: self.0,
+        }
+    }
+}
+
+/// A transport attaching headers to each request before passing it on
+#[derive(Debug, Clone)]
+struct HeaderService<S> {
+    inner: S,
+    headers: HeaderFn,
+}
+
+impl<S> Service<RequestPacket> for HeaderService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>,
+    S: Clone + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut packet: RequestPacket) -> Self::Future {
+        let headers = (self.headers)().into_iter().filter_map(|(k, v)| {
+            Some((
+                HeaderName::try_from(k).ok()?,
+                HeaderValue::try_from(v).ok()?,
+            ))
+        });
+        let headers: Vec<_> = headers.collect();
+
+        let requests = match &mut packet {
+            RequestPacket::Single(request) => std::slice::from_mut(request),
+            RequestPacket::Batch(requests) => requests.as_mut_slice(),
+        };
+        for request in requests {
+            request.headers_mut().extend(headers.iter().cloned());
+        }
+
+        Box::pin(self.inner.call(packet))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,4 +124,34 @@ mod tests {
         Client::new("http://localhost:8545")?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_client_headers() -> anyhow::Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A JSON-RPC node stand-in answering a single request
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let node = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await?;
+            let body = r#"{"jsonrpc":"2.0","id":0,"result":"0x2105"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await?;
+            anyhow::Ok(String::from_utf8_lossy(&request[..len]).to_lowercase())
+        });
+
+        let headers = || vec![(String::from("traceparent"), String::from("00-1-2-01"))];
+        let client = Client::with_headers(&url, headers)?;
+        assert_eq!(client.get_chain_id().await?, 8453);
+
+        // Verify the headers were sent
+        let request = node.await??;
+        assert!(request.contains("traceparent: 00-1-2-01"), "{request}");
+        Ok(())
+    }
 }
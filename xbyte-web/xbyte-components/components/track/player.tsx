@@ -16,7 +16,7 @@ import {
 import { Button } from "@/components/ui/button";
 import { Slider } from "@/components/ui/slider";
 import type { UUID } from "crypto";
-import { xByteClient } from "xbyte-sdk";
+import { API_PREFIX, xByteClient } from "xbyte-sdk";
 import { formatFromDecimals } from "@/lib/utils";
 import {
     InputGroup,
@@ -26,7 +26,7 @@ import {
 } from "@/components/ui/input-group";
 import { useXBytePrivy } from "@/hooks/useXBytePrivy";
 
-const PLAY_URL = `${process.env.NEXT_PUBLIC_XBYTE_URL}/s3/bucket`;
+const PLAY_URL = `${process.env.NEXT_PUBLIC_XBYTE_URL}${API_PREFIX}/s3/bucket`;
 
 /** The MIME types */
 export type MimeType = "audio/mpeg" | "video/mp4";
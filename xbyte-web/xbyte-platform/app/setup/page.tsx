@@ -185,6 +185,9 @@ const awsModalContent = {
     accountIdPlaceholder: "123456789012",
     accountIdHelper: "Your 12-digit AWS account ID",
     regionLabel: "AWS Region",
+    bucketsLabel: "Bucket Names",
+    bucketsPlaceholder: "my-movies, my-music",
+    bucketsHelper: "Comma-separated list of the buckets to monetize",
     connectButton: "Connect Storage",
     connecting: "Connecting...",
 };
@@ -389,6 +392,7 @@ function IntegrateProviderSection() {
     const [isModalOpen, setIsModalOpen] = useState(false);
     const [accountId, setAccountId] = useState("");
     const [region, setRegion] = useState("us-east-1");
+    const [bucketNames, setBucketNames] = useState("");
 
     const SelectIcon = () => (
         <div className="absolute top-2 right-2">
@@ -403,9 +407,13 @@ function IntegrateProviderSection() {
     };
 
     const isValidAccountId = accountId.length === 12 && /^\d+$/.test(accountId);
+    const requestedBuckets = bucketNames
+        .split(",")
+        .map((b) => b.trim())
+        .filter(Boolean);
 
     const handleConnect = async () => {
-        if (!isValidAccountId || !region) return;
+        if (!isValidAccountId || !region || requestedBuckets.length === 0) return;
 
         setIsLoading(true);
         setIsConnected(false);
@@ -440,6 +448,7 @@ function IntegrateProviderSection() {
                     },
                 },
                 client: client.id,
+                buckets: requestedBuckets,
             });
 
             if (registerStatus !== "Success") return setIsLoading(false);
@@ -580,12 +589,31 @@ function IntegrateProviderSection() {
                                 ))}
                             </select>
                         </div>
+
+                        <div className="space-y-2">
+                            <Label htmlFor="buckets">{awsModalContent.bucketsLabel}</Label>
+                            <Input
+                                id="buckets"
+                                placeholder={awsModalContent.bucketsPlaceholder}
+                                value={bucketNames}
+                                onChange={(e) => setBucketNames(e.target.value)}
+                                className="font-mono"
+                            />
+                            <p className="text-xs text-muted-foreground">
+                                {awsModalContent.bucketsHelper}
+                            </p>
+                        </div>
                     </div>
 
                     <DialogFooter>
                         <Button
                             onClick={handleConnect}
-                            disabled={isLoading || !isValidAccountId || !region}
+                            disabled={
+                                isLoading ||
+                                !isValidAccountId ||
+                                !region ||
+                                requestedBuckets.length === 0
+                            }
                             className="w-full sm:w-auto"
                         >
                             {isLoading ? (
@@ -1,6 +1,19 @@
-import { ApiResponse, Client, RegisterRequest, SetPriceRequest } from "./types";
+import {
+    ApiError,
+    ApiResponse,
+    Client,
+    ListObjectsRequest,
+    ObjectDetails,
+    ObjectListing,
+    Readiness,
+    RegisterRequest,
+    SetPriceRequest,
+    Versions,
+} from "./types";
 
 const DEFAULT_XBYTE_URL = "https://api.xbyte.sh";
+/** The prefix of the version of the xByte API the client speaks */
+export const API_PREFIX = "/v1";
 
 /**
  * The client for the xByte API
@@ -17,19 +30,27 @@ export class xByteClient {
     }
 
     private async request<T>(endpoint: string, options?: RequestInit): Promise<T> {
-        const response = await fetch(`${this.xbyteUrl}${endpoint}`, options);
+        const response = await fetch(`${this.xbyteUrl}${API_PREFIX}${endpoint}`, options);
         return response.json();
     }
 
-    async health(): Promise<ApiResponse<string, string>> {
+    async health(): Promise<ApiResponse<string, ApiError>> {
         return this.request("/health");
     }
 
     /**
-     * Get the version of the xByte API
-     * @returns The version of the xByte API
+     * Check whether the xByte API and its dependencies are ready
+     * @returns The status of each dependency, as an error when any is down
      */
-    async version(): Promise<ApiResponse<string, string>> {
+    async ready(): Promise<ApiResponse<Readiness, Readiness>> {
+        return this.request("/health/ready");
+    }
+
+    /**
+     * Get the versions supported by the xByte API
+     * @returns The server version and the API and x402 versions it supports
+     */
+    async version(): Promise<ApiResponse<Versions, ApiError>> {
         return this.request("/");
     }
 
@@ -38,7 +59,7 @@ export class xByteClient {
      * @param request The request to set the price
      * @returns The response from the xByte API
      */
-    async setPrice(request: SetPriceRequest): Promise<ApiResponse<string, string>> {
+    async setPrice(request: SetPriceRequest): Promise<ApiResponse<string, ApiError>> {
         const options: RequestInit = {
             method: "POST",
             headers: { "Content-Type": "application/json" },
@@ -54,7 +75,7 @@ export class xByteClient {
      * @param object The object to get the price from
      * @returns The price of the object
      */
-    async getPrice(bucket: string, object: string): Promise<ApiResponse<number, string>> {
+    async getPrice(bucket: string, object: string): Promise<ApiResponse<number, ApiError>> {
         return this.request(`/price/${bucket}/${object}`);
     }
 
@@ -63,7 +84,7 @@ export class xByteClient {
      * @param request The request to create a client
      * @returns The response from the xByte API
      */
-    async createClient(request: Client): Promise<ApiResponse<Client, string>> {
+    async createClient(request: Client): Promise<ApiResponse<Client, ApiError>> {
         const options: RequestInit = {
             method: "POST",
             headers: { "Content-Type": "application/json" },
@@ -78,7 +99,7 @@ export class xByteClient {
      * @param id The id of the client
      * @returns The response from the xByte API
      */
-    async getClient(id: string): Promise<ApiResponse<Client, string>> {
+    async getClient(id: string): Promise<ApiResponse<Client, ApiError>> {
         return this.request(`/client/${id}`);
     }
 
@@ -86,17 +107,34 @@ export class xByteClient {
      * Get all buckets
      * @returns The response from the xByte API
      */
-    async getAllBuckets(): Promise<ApiResponse<string[], string>> {
+    async getAllBuckets(): Promise<ApiResponse<string[], ApiError>> {
         return this.request("/s3/bucket");
     }
 
     /**
-     * Get all objects in a bucket
+     * Get a page of objects in a bucket
      * @param bucket The bucket to get the objects from
+     * @param request The prefix, delimiter, cursor and limit of the page
+     * @returns The response from the xByte API
+     */
+    async getAllObjects(bucket: string, request?: ListObjectsRequest): Promise<ApiResponse<ObjectListing, ApiError>> {
+        const params = new URLSearchParams();
+        for (const [key, value] of Object.entries(request ?? {})) {
+            if (value !== undefined) params.set(key, String(value));
+        }
+
+        const query = params.size > 0 ? `?${params}` : "";
+        return this.request(`/s3/bucket/${bucket}/objects${query}`);
+    }
+
+    /**
+     * Get the metadata and price of an object, without paying for it
+     * @param bucket The bucket of the object
+     * @param object The object to get the metadata of
      * @returns The response from the xByte API
      */
-    async getAllObjects(bucket: string): Promise<ApiResponse<string[], string>> {
-        return this.request(`/s3/bucket/${bucket}/objects`);
+    async getObjectInfo(bucket: string, object: string): Promise<ApiResponse<ObjectDetails, ApiError>> {
+        return this.request(`/s3/bucket/${bucket}/object/${object}/info`);
     }
 
     /**
@@ -104,7 +142,7 @@ export class xByteClient {
      * @param request The request to register storage
      * @returns The response from the xByte API
      */
-    async registerStorage(request: RegisterRequest): Promise<ApiResponse<string, string>> {
+    async registerStorage(request: RegisterRequest): Promise<ApiResponse<string, ApiError>> {
         const options: RequestInit = {
             method: "POST",
             headers: { "Content-Type": "application/json" },
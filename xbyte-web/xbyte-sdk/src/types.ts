@@ -1,13 +1,44 @@
-export type Storage = {
-    s3: {
-        role_arn: string;
-        region: string;
-    };
-};
+export type Storage =
+    | {
+          s3: {
+              role_arn: string;
+              region: string;
+          };
+      }
+    | {
+          s3Compatible: {
+              endpoint: string;
+              region: string;
+              access_key_id: string;
+              secret_access_key: string;
+              path_style?: boolean;
+          };
+      }
+    | {
+          gcs: {
+              project_id: string;
+              service_account_key?: string;
+              endpoint?: string;
+          };
+      }
+    | {
+          azureBlob: {
+              account: string;
+              access_key?: string;
+              sas_token?: string;
+              endpoint?: string;
+          };
+      }
+    | {
+          local: {
+              root: string;
+          };
+      };
 
 export interface RegisterRequest {
     storage: Storage;
     client: string;
+    buckets: string[];
 }
 
 export interface SetPriceRequest {
@@ -16,6 +47,32 @@ export interface SetPriceRequest {
     price: number;
 }
 
+export interface ListObjectsRequest {
+    prefix?: string;
+    delimiter?: string;
+    cursor?: string;
+    limit?: number;
+}
+
+export interface ObjectInfo {
+    key: string;
+    size: number;
+    lastModified: string | null;
+    etag: string | null;
+    contentType: string | null;
+    price: number;
+}
+
+export interface ObjectDetails extends ObjectInfo {
+    totalPrice: number;
+}
+
+export interface ObjectListing {
+    objects: ObjectInfo[];
+    prefixes: string[];
+    nextCursor: string | null;
+}
+
 export interface RangeRequest {
     offset: number;
     length: number;
@@ -29,6 +86,58 @@ export interface Client {
     storage?: Storage;
 }
 
+/**
+ * The machine-readable error codes of the xByte API
+ */
+export type ErrorCode =
+    | "invalid_request"
+    | "invalid_client_id"
+    | "no_buckets"
+    | "bucket_not_accessible"
+    | "invalid_payment"
+    | "payment_required"
+    | "bucket_not_owned"
+    | "client_not_found"
+    | "bucket_not_found"
+    | "bucket_owner_not_found"
+    | "object_not_found"
+    | "price_not_found"
+    | "client_exists"
+    | "bucket_already_registered"
+    | "range_not_satisfiable"
+    | "rate_limited"
+    | "internal_error"
+    | "storage_error"
+    | "facilitator_error";
+
+/**
+ * The error codes for which retrying the same request may succeed
+ */
+export const RETRYABLE_ERROR_CODES: readonly ErrorCode[] = [
+    "rate_limited",
+    "internal_error",
+    "storage_error",
+    "facilitator_error",
+];
+
+/**
+ * The error returned by xByte API
+ */
+export interface ApiError {
+    code: ErrorCode;
+    message: string;
+    details?: unknown;
+}
+
+/**
+ * Check whether retrying a failed request may succeed
+ * @param error The error returned by xByte API
+ * @returns Whether the request is worth retrying
+ */
+export function isRetryable(error: ApiError): boolean {
+    return RETRYABLE_ERROR_CODES.includes(error.code);
+}
+
 /**
  * The response from xByte API
  * @template T - The type of the data
@@ -54,3 +163,32 @@ export interface X402PaymentPayload {
         };
     };
 }
+
+/**
+ * The status of a dependency of xByte API
+ */
+export interface ComponentStatus {
+    up: boolean;
+    latencyMs: number;
+    error?: string;
+}
+
+/**
+ * The readiness of xByte API and its dependencies
+ */
+export interface Readiness {
+    ready: boolean;
+    components: Record<string, ComponentStatus>;
+}
+
+/**
+ * The versions supported by the xByte API
+ */
+export interface Versions {
+    /** The version of the server */
+    version: string;
+    /** The versions of the API, e.g. `v1` served under `/v1` */
+    api: string[];
+    /** The versions of the x402 protocol accepted for payments */
+    x402: number[];
+}